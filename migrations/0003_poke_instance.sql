@@ -0,0 +1,14 @@
+-- poke now only holds species data, every catch is an instance of a species
+create table poke_instance (
+    instance_id bigserial primary key,
+    poke_id bigint not null references poke (poke_id) on delete cascade,
+    instance_caught_at timestamptz not null default now(),
+    instance_nickname varchar,
+    instance_is_shiny boolean not null default false
+);
+
+-- Every existing row was one caught pokemon
+insert into poke_instance (poke_id, instance_is_shiny)
+select poke_id, coalesce(poke_is_shiny, false) from poke order by poke_id;
+
+alter table poke drop column poke_is_shiny;
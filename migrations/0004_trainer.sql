@@ -0,0 +1,15 @@
+-- Every caught instance belongs to a trainer
+create table trainer (
+    trainer_id bigserial primary key,
+    trainer_name varchar not null unique,
+    trainer_created_at timestamptz not null default now()
+);
+
+-- Existing catches go to the default trainer
+insert into trainer (trainer_name) values ('default');
+
+alter table poke_instance add column trainer_id bigint references trainer (trainer_id) on delete cascade;
+update poke_instance set trainer_id = (select trainer_id from trainer where trainer_name = 'default');
+alter table poke_instance alter column trainer_id set not null;
+
+create index poke_instance_trainer_idx on poke_instance (trainer_id, poke_id);
@@ -0,0 +1,6 @@
+-- Proof of a shiny hunt, the hash of the instance identity with the nonce
+-- starts with <difficulty> times <number>
+-- Shinies from before proofs were stored have no proof
+alter table poke_instance add column instance_shiny_nonce bigint;
+alter table poke_instance add column instance_shiny_difficulty integer;
+alter table poke_instance add column instance_shiny_number integer;
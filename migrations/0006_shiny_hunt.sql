@@ -0,0 +1,11 @@
+-- Checkpoint of an unfinished shiny hunt, at most one per caught pokemon
+-- hunt_frontier holds the next nonce of each worker, the number of workers
+-- is the length of the array
+create table shiny_hunt (
+    instance_id bigint primary key references poke_instance (instance_id) on delete cascade,
+    hunt_difficulty integer not null,
+    hunt_number integer not null,
+    hunt_frontier bigint[] not null,
+    hunt_attempts bigint not null default 0,
+    hunt_updated_at timestamptz not null default now()
+);
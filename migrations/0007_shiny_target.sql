@@ -0,0 +1,14 @@
+-- Shiny targets are now spec strings (prefix:000, suffix:cafe, below:4.5)
+-- Old targets were <difficulty> times <number> at the start of the hash
+alter table poke_instance add column instance_shiny_target varchar;
+update poke_instance
+set instance_shiny_target = 'prefix:' || repeat(instance_shiny_number::text, instance_shiny_difficulty)
+where instance_shiny_difficulty is not null and instance_shiny_number is not null;
+alter table poke_instance drop column instance_shiny_difficulty;
+alter table poke_instance drop column instance_shiny_number;
+
+alter table shiny_hunt add column hunt_target varchar;
+update shiny_hunt set hunt_target = 'prefix:' || repeat(hunt_number::text, hunt_difficulty);
+alter table shiny_hunt alter column hunt_target set not null;
+alter table shiny_hunt drop column hunt_difficulty;
+alter table shiny_hunt drop column hunt_number;
@@ -0,0 +1,4 @@
+-- How an instance became shiny : 'hunt' (mined with the shiny command) or
+-- 'encounter' (random roll when caught)
+alter table poke_instance add column instance_shiny_source varchar;
+update poke_instance set instance_shiny_source = 'hunt' where instance_is_shiny;
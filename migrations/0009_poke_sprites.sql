@@ -0,0 +1,11 @@
+-- Sprite URLs of the species (front and back, default and shiny, official artwork)
+-- Older rows get them with catch --refresh
+alter table poke add column poke_sprites json;
+
+-- Images downloaded with catch --store-sprites, keyed by URL so the
+-- collection can draw sprites without the API
+create table sprite_image (
+    image_url varchar primary key,
+    image_bytes bytea not null,
+    image_fetched_at timestamptz not null default now()
+);
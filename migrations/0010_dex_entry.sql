@@ -0,0 +1,8 @@
+-- Local copy of pokemon data imported with import-dex (PokeAPI json format),
+-- used before the API so catch, info and dex work offline
+create table dex_entry (
+    dex_id bigint primary key,
+    dex_name varchar not null unique,
+    dex_data json not null,
+    dex_imported_at timestamptz not null default now()
+);
@@ -0,0 +1,390 @@
+use std::env;
+
+use reqwest::{
+    header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RETRY_AFTER},
+    Client, ClientBuilder, StatusCode,
+};
+
+use crate::{
+    cache::{ResponseCache, Validators},
+    error::PokeError,
+    poke::ResourceList,
+    throttle::{ApiLimits, TokenBucket},
+};
+
+// Wrapper around reqwest to query pokeapi (or any mirror of it)
+// Every clone shares the same rate limit
+
+pub const DEFAULT_API_URL: &str = "https://pokeapi.co/api/v2/";
+
+#[derive(Clone)]
+pub struct PokeApi {
+    client: Client,
+    base_url: String,
+    cache: Option<ResponseCache>,
+    offline: bool,
+    limits: ApiLimits,
+    bucket: TokenBucket,
+}
+
+impl PokeApi {
+    pub fn new(client: Client, base_url: &str) -> Self {
+        // Always keep a trailing slash so paths can be appended directly
+        let base_url = if base_url.ends_with('/') {
+            base_url.to_string()
+        } else {
+            format!("{}/", base_url)
+        };
+
+        let limits = ApiLimits::default();
+        PokeApi {
+            client,
+            base_url,
+            cache: None,
+            offline: false,
+            bucket: TokenBucket::new(limits.rate, limits.burst),
+            limits,
+        }
+    }
+
+    // Base URL comes from POKEAPI_URL (next to DB_URL), default to the public API
+    pub fn from_env(client: Client) -> Self {
+        let base_url = env::var("POKEAPI_URL").unwrap_or_else(|_| DEFAULT_API_URL.to_string());
+        PokeApi::new(client, &base_url).with_limits(ApiLimits::from_env())
+    }
+
+    pub fn with_limits(mut self, limits: ApiLimits) -> Self {
+        self.bucket = TokenBucket::new(limits.rate, limits.burst);
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    // Offline: only serve from cache, whatever the age of the entry
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    pub fn default_client() -> Client {
+        ClientBuilder::new()
+            .connect_timeout(std::time::Duration::from_secs(10))
+            .build()
+            .unwrap_or_default()
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path.trim_start_matches('/'))
+    }
+
+    // Raw body of a resource, from cache when possible
+    pub async fn fetch(&self, path: &str) -> Result<Vec<u8>, PokeError> {
+        self.fetch_url(&self.url(path)).await
+    }
+
+    // Same for an absolute URL, sprites are served outside of the API base URL
+    pub async fn fetch_url(&self, url: &str) -> Result<Vec<u8>, PokeError> {
+        self.fetch_cached(url, false).await
+    }
+
+    // Asks the API even if the cached entry is fresh, with a conditional request
+    // when the entry has an ETag or a Last-Modified
+    pub async fn revalidate(&self, path: &str) -> Result<Vec<u8>, PokeError> {
+        self.fetch_cached(&self.url(path), true).await
+    }
+
+    pub async fn revalidate_pokemon(&self, name: &str) -> Result<Vec<u8>, PokeError> {
+        self.revalidate(&format!("pokemon/{}", name))
+            .await
+            .map_err(|e| match e {
+                PokeError::NotFound(_) => PokeError::UnknownPokemon(name.to_string()),
+                e => e,
+            })
+    }
+
+    async fn fetch_cached(&self, url: &str, revalidate: bool) -> Result<Vec<u8>, PokeError> {
+        let url = url.to_string();
+
+        // Stale entries are revalidated too
+        let (cached, fresh) = match &self.cache {
+            Some(cache) => {
+                let entry = cache.get(&url).await;
+                let fresh = entry
+                    .as_ref()
+                    .is_some_and(|entry| entry.is_fresh(cache.ttl()));
+                (entry, fresh)
+            }
+            None => (None, false),
+        };
+        let validators = match cached {
+            Some(entry) if self.offline || (fresh && !revalidate) => return Ok(entry.body),
+            Some(ref entry) => entry.validators.clone(),
+            None => Validators::default(),
+        };
+
+        if self.offline {
+            return Err(PokeError::OfflineMiss(url));
+        }
+
+        let mut attempt = 0;
+        let response = loop {
+            let (error, retry_after) = match self.get(&url, &validators).await {
+                Ok(response) => break response,
+                Err(Attempt::Retry(error, retry_after)) => (error, retry_after),
+                Err(Attempt::Fail(error)) => return Err(error),
+            };
+            match self.limits.retry_delay(attempt, retry_after) {
+                Some(delay) => {
+                    eprintln!("{}, retrying {} in {:.1}s", error, url, delay.as_secs_f64());
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                None => return Err(error),
+            }
+        };
+
+        let (body, validators) = match (response, cached) {
+            (Response::NotModified, Some(entry)) => (entry.body, validators),
+            (Response::NotModified, None) => {
+                return Err(PokeError::ApiUnavailable(format!(
+                    "Not modified answer without cached response for {}",
+                    url
+                )))
+            }
+            (Response::Body(body, validators), _) => (body, validators),
+        };
+
+        if let Some(cache) = &self.cache {
+            // A failing cache should not fail the query
+            if let Err(e) = cache.put_validated(&url, &body, &validators).await {
+                eprintln!("Could not write cache for {}: {}", url, e);
+            }
+        }
+
+        Ok(body)
+    }
+
+    // One attempt, after waiting for the rate limit
+    async fn get(&self, url: &str, validators: &Validators) -> Result<Response, Attempt> {
+        self.bucket.acquire().await;
+        let retry = |e: reqwest::Error| Attempt::Retry(e.into(), None);
+
+        let mut request = self.client.get(url).timeout(self.limits.timeout);
+        if let Some(etag) = &validators.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+
+        let rep = request.send().await.map_err(retry)?;
+        match rep.status() {
+            StatusCode::NOT_MODIFIED => Ok(Response::NotModified),
+            status if status.is_success() => {
+                let header = |name| {
+                    rep.headers()
+                        .get(name)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string)
+                };
+                let validators = Validators {
+                    etag: header(ETAG),
+                    last_modified: header(LAST_MODIFIED),
+                };
+                let body = rep.bytes().await.map_err(retry)?.to_vec();
+                Ok(Response::Body(body, validators))
+            }
+            StatusCode::NOT_FOUND => Err(Attempt::Fail(PokeError::NotFound(url.to_string()))),
+            StatusCode::TOO_MANY_REQUESTS => {
+                let retry_after = rep
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse().ok());
+                Err(Attempt::Retry(
+                    PokeError::RateLimited { retry_after },
+                    retry_after,
+                ))
+            }
+            status => {
+                let error = PokeError::ApiUnavailable(format!("API Response error: {}", status));
+                // Other client errors would fail the same way again
+                if status.is_server_error() {
+                    Err(Attempt::Retry(error, None))
+                } else {
+                    Err(Attempt::Fail(error))
+                }
+            }
+        }
+    }
+
+    pub async fn fetch_pokemon(&self, name: &str) -> Result<Vec<u8>, PokeError> {
+        self.fetch(&format!("pokemon/{}", name))
+            .await
+            .map_err(|e| match e {
+                PokeError::NotFound(_) => PokeError::UnknownPokemon(name.to_string()),
+                e => e,
+            })
+    }
+
+    // Species of a pokedex range, national dex numbers start at 1
+    pub async fn fetch_species_list(
+        &self,
+        start: i64,
+        end: i64,
+    ) -> Result<ResourceList, PokeError> {
+        let body = self
+            .fetch(&format!(
+                "pokemon-species?offset={}&limit={}",
+                start - 1,
+                end - start + 1
+            ))
+            .await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+enum Response {
+    Body(Vec<u8>, Validators),
+    NotModified,
+}
+
+// Failed attempt, Retry holds the Retry-After of the server if any
+enum Attempt {
+    Retry(PokeError, Option<u64>),
+    Fail(PokeError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_join() {
+        let api = PokeApi::new(Client::new(), "http://localhost:8080/api/v2");
+        assert_eq!(api.base_url(), "http://localhost:8080/api/v2/");
+        assert_eq!(
+            api.url("pokemon/pikachu"),
+            "http://localhost:8080/api/v2/pokemon/pikachu"
+        );
+        assert_eq!(
+            api.url("/pokemon/pikachu"),
+            "http://localhost:8080/api/v2/pokemon/pikachu"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_offline_fetch() {
+        let dir = env::temp_dir().join(format!("poke-collect-offline-test-{}", std::process::id()));
+        let cache = ResponseCache::new(dir.clone(), std::time::Duration::ZERO);
+        // Unroutable URL, offline mode must never reach it
+        let api = PokeApi::new(Client::new(), "http://127.0.0.1:9/api/v2/")
+            .with_cache(cache.clone())
+            .offline(true);
+
+        assert!(matches!(
+            api.fetch_pokemon("pikachu").await,
+            Err(PokeError::OfflineMiss(_))
+        ));
+
+        // Stale entry (ttl 0) is still served when offline
+        cache.put(&api.url("pokemon/pikachu"), b"{}").await.unwrap();
+        assert_eq!(api.fetch_pokemon("pikachu").await.unwrap(), b"{}");
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_fetch_uses_cache() {
+        let mock = crate::mock_api::MockPokeApi::start().await;
+        let dir = env::temp_dir().join(format!("poke-collect-fetch-test-{}", std::process::id()));
+        let api = mock.api().with_cache(ResponseCache::new(
+            dir.clone(),
+            std::time::Duration::from_secs(60),
+        ));
+
+        api.fetch_pokemon("pikachu").await.unwrap();
+        api.fetch_pokemon("pikachu").await.unwrap();
+        assert_eq!(mock.request_count().await, 1);
+
+        // 404 is an error and is never cached
+        for _ in 0..2 {
+            assert!(matches!(
+                api.fetch_pokemon("missingno").await,
+                Err(PokeError::UnknownPokemon(_))
+            ));
+        }
+        assert_eq!(mock.request_count().await, 3);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_fetch_retries() {
+        let mock = crate::mock_api::MockPokeApi::start().await;
+        let api = mock.api();
+
+        // 2 failures then the fixture, within the 3 retries
+        mock.fail_times("pokemon", "pikachu", 503, 2).await;
+        api.fetch_pokemon("pikachu").await.unwrap();
+        assert_eq!(mock.request_count().await, 3);
+
+        // Retries exhausted
+        mock.fail_times("pokemon", "eevee", 500, 4).await;
+        assert!(matches!(
+            api.fetch_pokemon("eevee").await,
+            Err(PokeError::ApiUnavailable(_))
+        ));
+        assert_eq!(mock.request_count().await, 7);
+
+        // Retry-After 0 is honored right away
+        mock.rate_limited("pokemon", "mew", Some(0)).await;
+        assert!(matches!(
+            api.fetch_pokemon("mew").await,
+            Err(PokeError::RateLimited {
+                retry_after: Some(0)
+            })
+        ));
+        assert_eq!(mock.request_count().await, 11);
+
+        // Other client errors are not retried
+        mock.fail_with("pokemon", "bulbasaur", 400).await;
+        assert!(matches!(
+            api.fetch_pokemon("bulbasaur").await,
+            Err(PokeError::ApiUnavailable(_))
+        ));
+        assert_eq!(mock.request_count().await, 12);
+    }
+
+    #[tokio::test]
+    async fn test_revalidate() {
+        let mock = crate::mock_api::MockPokeApi::start().await;
+        let dir = env::temp_dir().join(format!("poke-collect-etag-test-{}", std::process::id()));
+        let api = mock.api().with_cache(ResponseCache::new(
+            dir.clone(),
+            std::time::Duration::from_secs(60),
+        ));
+
+        let body = api.fetch_pokemon("pikachu").await.unwrap();
+        // Fresh entry, the server answers 304 to the conditional request
+        assert_eq!(api.revalidate_pokemon("pikachu").await.unwrap(), body);
+        assert_eq!(mock.request_count().await, 2);
+
+        let mut json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        json["base_experience"] = 120.into();
+        mock.update("pokemon", "pikachu", &json).await;
+        let updated = api.revalidate_pokemon("pikachu").await.unwrap();
+        assert_ne!(updated, body);
+        assert_eq!(api.fetch_pokemon("pikachu").await.unwrap(), updated);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+// Persistent cache of raw API responses, keyed by URL
+// ETag and Last-Modified of a response are kept in a .meta file next to it,
+// so a stale entry can be revalidated with a conditional request
+
+// One week, pokeapi data barely changes
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+pub struct CacheEntry {
+    pub body: Vec<u8>,
+    pub age: Duration,
+    pub validators: Validators,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+impl CacheEntry {
+    pub fn is_fresh(&self, ttl: Duration) -> bool {
+        self.age <= ttl
+    }
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf, ttl: Duration) -> Self {
+        ResponseCache { dir, ttl }
+    }
+
+    // CACHE_DIR and CACHE_TTL (in seconds) can be set next to DB_URL
+    pub fn from_env() -> Self {
+        let dir = env::var("CACHE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| default_cache_dir());
+        let ttl = env::var("CACHE_TTL")
+            .ok()
+            .and_then(|ttl| ttl.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_CACHE_TTL);
+
+        ResponseCache::new(dir, ttl)
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    // File name is the hash of the URL so any URL maps to a valid path
+    fn path(&self, url: &str) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(url);
+        self.dir.join(format!("{:x}", hasher.finalize()))
+    }
+
+    // Returns the entry whatever its age, caller decides with is_fresh
+    pub async fn get(&self, url: &str) -> Option<CacheEntry> {
+        let path = self.path(url);
+        let modified = fs::metadata(&path).await.ok()?.modified().ok()?;
+        let body = fs::read(&path).await.ok()?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+
+        // Entries written before validators were kept have none
+        let validators = fs::read(path.with_extension("meta"))
+            .await
+            .ok()
+            .and_then(|meta| serde_json::from_slice(&meta).ok())
+            .unwrap_or_default();
+
+        Some(CacheEntry {
+            body,
+            age,
+            validators,
+        })
+    }
+
+    pub async fn put(&self, url: &str, body: &[u8]) -> std::io::Result<()> {
+        self.put_validated(url, body, &Validators::default()).await
+    }
+
+    // Also used to reset the age of an entry the server says is still valid
+    pub async fn put_validated(
+        &self,
+        url: &str,
+        body: &[u8],
+        validators: &Validators,
+    ) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir).await?;
+
+        let path = self.path(url);
+        let meta_path = path.with_extension("meta");
+        if validators.is_empty() {
+            let _ = fs::remove_file(&meta_path).await;
+        } else {
+            write_atomic(&meta_path, &serde_json::to_vec(validators)?).await?;
+        }
+        write_atomic(&path, body).await
+    }
+}
+
+// Numbers the temp files, concurrent writes of the same URL in one process
+// each get their own
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Write then rename so a concurrent reader never sees a partial file
+async fn write_atomic(path: &Path, body: &[u8]) -> std::io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(format!(
+        ".tmp{}-{}",
+        std::process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&tmp_path, body).await?;
+    fs::rename(&tmp_path, path).await
+}
+
+fn default_cache_dir() -> PathBuf {
+    if let Ok(xdg) = env::var("XDG_CACHE_HOME") {
+        return PathBuf::from(xdg).join("poke-collect");
+    }
+    if let Ok(home) = env::var("HOME") {
+        return PathBuf::from(home).join(".cache").join("poke-collect");
+    }
+    env::temp_dir().join("poke-collect")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cache_put_get() {
+        let dir = env::temp_dir().join(format!("poke-collect-cache-test-{}", std::process::id()));
+        let cache = ResponseCache::new(dir.clone(), Duration::from_secs(60));
+        let url = "http://localhost/api/v2/pokemon/pikachu";
+
+        assert!(cache.get(url).await.is_none());
+
+        cache.put(url, b"{\"name\":\"pikachu\"}").await.unwrap();
+        let entry = cache.get(url).await.expect("Entry should be cached");
+        assert_eq!(entry.body, b"{\"name\":\"pikachu\"}");
+        assert!(entry.is_fresh(cache.ttl()));
+        assert!(entry.validators.is_empty());
+
+        let validators = Validators {
+            etag: Some(String::from("\"v2\"")),
+            last_modified: None,
+        };
+        cache.put_validated(url, b"{}", &validators).await.unwrap();
+        let entry = cache.get(url).await.expect("Entry should be cached");
+        assert_eq!(entry.body, b"{}");
+        assert_eq!(entry.validators, validators);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_cache_concurrent_put() {
+        let dir = env::temp_dir().join(format!(
+            "poke-collect-cache-concurrent-test-{}",
+            std::process::id()
+        ));
+        let cache = ResponseCache::new(dir.clone(), Duration::from_secs(60));
+        let url = "http://localhost/sprites/25.png";
+
+        // Same URL written by several tasks at once, none of the renames fails
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let cache = cache.clone();
+                tokio::spawn(async move { cache.put(url, b"png").await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        assert_eq!(cache.get(url).await.unwrap().body, b"png");
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,294 @@
+use core::fmt;
+
+use serde::Serialize;
+
+use crate::{error::PokeError, generation::Generation, output::Record, poke::DbPoke};
+
+// Result of each name of a multi catch
+
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CatchStatus {
+    Caught,
+    Refreshed,
+    AlreadyOwned,
+    NotFound,
+    ApiError,
+    Failed,
+}
+
+impl CatchStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CatchStatus::Caught => "caught",
+            CatchStatus::Refreshed => "refreshed",
+            CatchStatus::AlreadyOwned => "already-owned",
+            CatchStatus::NotFound => "not-found",
+            CatchStatus::ApiError => "api-error",
+            CatchStatus::Failed => "failed",
+        }
+    }
+
+    // Already owned is a skip, not a failure
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            CatchStatus::NotFound | CatchStatus::ApiError | CatchStatus::Failed
+        )
+    }
+}
+
+#[derive(Serialize)]
+pub struct CatchReport {
+    pub name: String,
+    pub status: CatchStatus,
+    pub id: Option<i64>,
+    pub instance: Option<i64>,
+    pub is_shiny: bool,
+    pub error: Option<String>,
+}
+
+impl CatchReport {
+    pub fn new(name: &str, result: Result<DbPoke, PokeError>) -> Self {
+        let mut report = CatchReport {
+            name: name.to_string(),
+            status: CatchStatus::Caught,
+            id: None,
+            instance: None,
+            is_shiny: false,
+            error: None,
+        };
+
+        match result {
+            Ok(poke) => {
+                // Refreshed species come back without a new instance
+                let instance = poke.instances.first();
+                report.status = if instance.is_some() {
+                    CatchStatus::Caught
+                } else {
+                    CatchStatus::Refreshed
+                };
+                report.name = poke.name.clone();
+                report.id = Some(poke.id);
+                report.instance = instance.map(|instance| instance.id);
+                report.is_shiny = instance.is_some_and(|instance| instance.is_shiny);
+            }
+            Err(e) => {
+                report.status = match e {
+                    PokeError::AlreadyCaught(_) => CatchStatus::AlreadyOwned,
+                    PokeError::UnknownPokemon(_) | PokeError::NotFound(_) => CatchStatus::NotFound,
+                    PokeError::ApiUnavailable(_)
+                    | PokeError::RateLimited { .. }
+                    | PokeError::OfflineMiss(_) => CatchStatus::ApiError,
+                    _ => CatchStatus::Failed,
+                };
+                report.error = Some(e.to_string());
+            }
+        }
+        report
+    }
+}
+
+impl fmt::Display for CatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<16} {}", self.name, self.status.as_str())?;
+        if let Some(instance) = self.instance {
+            write!(f, " #{}", instance)?;
+        }
+        if self.is_shiny {
+            write!(f, " (shiny)")?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, " : {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Record for CatchReport {
+    fn headers() -> Vec<&'static str> {
+        vec!["name", "status", "id", "instance", "is_shiny", "error"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.status.as_str().to_string(),
+            self.id.map(|id| id.to_string()).unwrap_or_default(),
+            self.instance.map(|id| id.to_string()).unwrap_or_default(),
+            self.is_shiny.to_string(),
+            self.error.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+// Species returned by a single catch, with the new instance or none when
+// the species was only refreshed
+#[derive(Serialize)]
+#[serde(transparent)]
+pub struct CatchOutcome(pub DbPoke);
+
+impl fmt::Display for CatchOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let poke = &self.0;
+        if !poke.instances.is_empty() {
+            return write!(f, "{}", poke);
+        }
+        poke.fmt_species(f)?;
+        writeln!(f, "- Refreshed")
+    }
+}
+
+impl Record for CatchOutcome {
+    fn headers() -> Vec<&'static str> {
+        DbPoke::headers()
+    }
+
+    fn row(&self) -> Vec<String> {
+        self.0.row()
+    }
+}
+
+// Dex numbers are written without leading zeros and names in lowercase, like
+// the API and the database
+pub fn normalize_name(name: &str) -> String {
+    match name.trim().parse::<i64>() {
+        Ok(id) => id.to_string(),
+        Err(_) => name.trim().to_lowercase(),
+    }
+}
+
+// Names, dex numbers and ranges of dex numbers (1-151), in the given order
+// Names with a dash (ho-oh) are kept, only two numbers make a range
+// Ranges stop at the last pokemon of the last generation
+pub fn expand_names(inputs: &[String]) -> Result<Vec<String>, PokeError> {
+    let last = Generation::ALL[Generation::ALL.len() - 1].end();
+    let mut names = Vec::new();
+    for input in inputs {
+        let range = input.split_once('-').and_then(|(start, end)| {
+            Some((
+                start.trim().parse::<i64>().ok()?,
+                end.trim().parse::<i64>().ok()?,
+            ))
+        });
+        match range {
+            Some((start, end)) if start >= 1 && start <= end && end <= last => {
+                names.extend((start..=end).map(|id| id.to_string()))
+            }
+            Some(_) => {
+                return Err(PokeError::Config(format!(
+                    "Invalid dex range {}, use <first>-<last> with 1 <= first <= last <= {}",
+                    input, last
+                )))
+            }
+            None => names.push(normalize_name(input)),
+        }
+    }
+    Ok(names)
+}
+
+// Count of each status, in the order of CatchStatus
+pub fn summary(reports: &[CatchReport]) -> String {
+    let statuses = [
+        CatchStatus::Caught,
+        CatchStatus::Refreshed,
+        CatchStatus::AlreadyOwned,
+        CatchStatus::NotFound,
+        CatchStatus::ApiError,
+        CatchStatus::Failed,
+    ];
+    statuses
+        .iter()
+        .filter_map(|status| {
+            let count = reports.iter().filter(|r| r.status == *status).count();
+            (count > 0).then(|| format!("{} {}", count, status.as_str()))
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poke::PokeInstance;
+    use chrono::Utc;
+
+    #[test]
+    fn test_expand_names() {
+        let inputs: Vec<String> = ["Pikachu", "025", "1-3", "ho-oh", "151-151"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            expand_names(&inputs).unwrap(),
+            ["pikachu", "25", "1", "2", "3", "ho-oh", "151"]
+        );
+        assert_eq!(expand_names(&[String::from("1-1025")]).unwrap().len(), 1025);
+        for range in ["3-1", "0-5", "1-1026", "1-100000000"] {
+            assert!(matches!(
+                expand_names(&[range.to_string()]),
+                Err(PokeError::Config(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_catch_outcome() {
+        let mut outcome = CatchOutcome(DbPoke {
+            id: 25,
+            name: String::from("pikachu"),
+            types: Vec::new(),
+            base_experience: 112,
+            stats: Vec::new(),
+            sprites: Default::default(),
+            instances: Vec::new(),
+        });
+        let refreshed = outcome.to_string();
+        assert!(refreshed.ends_with("- Refreshed\n"));
+        assert!(!refreshed.contains("Caught"));
+
+        outcome.0.instances.push(PokeInstance {
+            id: 1,
+            caught_at: Utc::now(),
+            nickname: None,
+            is_shiny: false,
+            shiny_source: None,
+            shiny_proof: None,
+        });
+        assert!(outcome.to_string().contains("- Caught : 1 (0 shiny)"));
+    }
+
+    #[test]
+    fn test_report_status() {
+        let reports = [
+            CatchReport::new("pikachu", Err(PokeError::AlreadyCaught("pikachu".into()))),
+            CatchReport::new(
+                "missingno",
+                Err(PokeError::UnknownPokemon("missingno".into())),
+            ),
+            CatchReport::new("mew", Err(PokeError::RateLimited { retry_after: None })),
+            CatchReport::new("eevee", Err(PokeError::UnknownPokemon("eevee".into()))),
+        ];
+        let statuses: Vec<CatchStatus> = reports.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                CatchStatus::AlreadyOwned,
+                CatchStatus::NotFound,
+                CatchStatus::ApiError,
+                CatchStatus::NotFound
+            ]
+        );
+        assert!(!statuses[0].is_failure() && statuses[1].is_failure());
+        assert_eq!(
+            summary(&reports),
+            "1 already-owned, 2 not-found, 1 api-error"
+        );
+        assert_eq!(
+            reports[1].to_string(),
+            "missingno        not-found : Unknown pokemon: missingno"
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use core::fmt;
+
+use serde::{Serialize, Serializer};
+
+use crate::{generation::Generation, output::Record};
+
+// Pokedex completion of one generation
+
+#[derive(Serialize)]
+pub struct DexGeneration {
+    #[serde(serialize_with = "serialize_gen")]
+    pub gen: Generation,
+    pub total: i64,
+    pub caught: i64,
+    pub shiny: i64,
+    // National dex number and name (when the API could give it)
+    #[serde(serialize_with = "serialize_missing")]
+    pub missing: Vec<(i64, Option<String>)>,
+}
+
+fn serialize_gen<S: Serializer>(gen: &Generation, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(gen.number() as u64)
+}
+
+#[derive(Serialize)]
+struct MissingOutput<'a> {
+    id: i64,
+    name: Option<&'a str>,
+}
+
+fn serialize_missing<S: Serializer>(
+    missing: &[(i64, Option<String>)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(missing.iter().map(|(id, name)| MissingOutput {
+        id: *id,
+        name: name.as_deref(),
+    }))
+}
+
+impl Record for DexGeneration {
+    fn headers() -> Vec<&'static str> {
+        vec![
+            "gen",
+            "region",
+            "caught",
+            "total",
+            "caught_percent",
+            "shiny",
+            "shiny_percent",
+            "missing",
+        ]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.gen.number().to_string(),
+            self.gen.region().to_string(),
+            self.caught.to_string(),
+            self.total.to_string(),
+            format!("{:.1}", self.caught_percent()),
+            self.shiny.to_string(),
+            format!("{:.1}", self.shiny_percent()),
+            self.missing
+                .iter()
+                .map(|(id, _)| id.to_string())
+                .collect::<Vec<String>>()
+                .join("|"),
+        ]
+    }
+}
+
+impl DexGeneration {
+    pub fn caught_percent(&self) -> f64 {
+        percent(self.caught, self.total)
+    }
+
+    pub fn shiny_percent(&self) -> f64 {
+        percent(self.shiny, self.total)
+    }
+}
+
+fn percent(count: i64, total: i64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 * 100.0 / total as f64
+    }
+}
+
+impl fmt::Display for DexGeneration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} : {}/{} ({:.1}%) - shiny {}/{} ({:.1}%)",
+            self.gen,
+            self.caught,
+            self.total,
+            self.caught_percent(),
+            self.shiny,
+            self.total,
+            self.shiny_percent()
+        )?;
+
+        for (id, name) in &self.missing {
+            match name {
+                Some(name) => write!(f, "\n  #{:04} {}", id, name)?,
+                None => write!(f, "\n  #{:04}", id)?,
+            }
+        }
+        Ok(())
+    }
+}
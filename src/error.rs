@@ -0,0 +1,152 @@
+use thiserror::Error;
+
+// Every failure the CLI can report
+// Each kind has its own process exit code so wrapper scripts can branch on it :
+// 0  success
+// 1  unexpected error (DB query, invalid data, IO...)
+// 2  invalid usage or configuration (also used by clap for bad arguments)
+// 3  unknown pokemon (404 from the API)
+// 4  API unavailable (network error, 5xx...)
+// 5  API rate limit reached (429)
+// 6  database unavailable
+// 7  pokemon already in the collection
+// 8  pokemon not in the collection
+// 9  offline mode and no cached response
+// 10 unknown trainer
+// 11 trainer already exists
+// 12 shiny proof missing or invalid
+// 13 shiny hunt timed out
+// 14 no saved shiny hunt to resume
+// 15 some catches of a multi catch (or some refreshes) failed
+// 130 shiny hunt interrupted (Ctrl-C), progress is saved
+
+#[derive(Debug, Error)]
+pub enum PokeError {
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("Unknown pokemon: {0}")]
+    UnknownPokemon(String),
+
+    #[error("Resource not found on the API: {0}")]
+    NotFound(String),
+
+    #[error("PokeAPI unavailable: {0}")]
+    ApiUnavailable(String),
+
+    #[error("PokeAPI rate limit reached{}", retry_after_message(.retry_after))]
+    RateLimited { retry_after: Option<u64> },
+
+    #[error("Database unavailable: {0}")]
+    DbUnavailable(sqlx::Error),
+
+    #[error("{0} is already in your collection")]
+    AlreadyCaught(String),
+
+    #[error("{0} is not in your collection")]
+    NotInCollection(String),
+
+    #[error("Offline mode: no cached response for {0}")]
+    OfflineMiss(String),
+
+    #[error("Unknown trainer: {0}")]
+    UnknownTrainer(String),
+
+    #[error("Trainer {0} already exists")]
+    TrainerExists(String),
+
+    #[error("Invalid shiny proof: {0}")]
+    InvalidShinyProof(String),
+
+    #[error("Database error: {0}")]
+    Db(sqlx::Error),
+
+    #[error("Database migration error: {0}")]
+    Migration(#[from] sqlx::migrate::MigrateError),
+
+    #[error("Invalid data: {0}")]
+    InvalidData(#[from] serde_json::Error),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Shiny hunt stopped after {0}s without finding a shiny")]
+    HuntTimeout(u64),
+
+    #[error("Shiny hunt interrupted, resume it with shiny --resume")]
+    HuntInterrupted,
+
+    #[error("No saved shiny hunt for {0}")]
+    NoSavedHunt(String),
+
+    #[error("{failed} of {total} catches failed")]
+    CatchFailed { failed: usize, total: usize },
+
+    #[error("{failed} of {total} refreshes failed")]
+    RefreshFailed { failed: usize, total: usize },
+
+    #[error("Shiny hunt failed: {0}")]
+    ShinyHunt(String),
+
+    #[error("Invalid image: {0}")]
+    Image(#[from] image::ImageError),
+
+    #[error("Task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+impl PokeError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            PokeError::Config(_) => 2,
+            PokeError::UnknownPokemon(_) | PokeError::NotFound(_) => 3,
+            PokeError::ApiUnavailable(_) => 4,
+            PokeError::RateLimited { .. } => 5,
+            PokeError::DbUnavailable(_) => 6,
+            PokeError::AlreadyCaught(_) => 7,
+            PokeError::NotInCollection(_) => 8,
+            PokeError::OfflineMiss(_) => 9,
+            PokeError::UnknownTrainer(_) => 10,
+            PokeError::TrainerExists(_) => 11,
+            PokeError::InvalidShinyProof(_) => 12,
+            PokeError::HuntTimeout(_) => 13,
+            PokeError::NoSavedHunt(_) => 14,
+            PokeError::CatchFailed { .. } | PokeError::RefreshFailed { .. } => 15,
+            PokeError::HuntInterrupted => 130,
+            PokeError::Db(_)
+            | PokeError::Migration(_)
+            | PokeError::InvalidData(_)
+            | PokeError::Io(_)
+            | PokeError::ShinyHunt(_)
+            | PokeError::Image(_)
+            | PokeError::Task(_) => 1,
+        }
+    }
+}
+
+fn retry_after_message(retry_after: &Option<u64>) -> String {
+    match retry_after {
+        Some(secs) => format!(", retry after {}s", secs),
+        None => String::new(),
+    }
+}
+
+// Connection problems are told apart from failing queries
+impl From<sqlx::Error> for PokeError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::Configuration(_) => PokeError::DbUnavailable(e),
+            _ => PokeError::Db(e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for PokeError {
+    fn from(e: reqwest::Error) -> Self {
+        PokeError::ApiUnavailable(e.to_string())
+    }
+}
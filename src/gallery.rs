@@ -0,0 +1,220 @@
+use std::{collections::HashMap, fmt::Write, path::Path};
+
+use crate::{
+    generation::Generation,
+    poke::{DbPoke, STAT_NAMES},
+};
+
+// Static HTML gallery of a collection
+// index.html has every card, gen-<n>.html the cards of one generation
+// Images are copied next to the pages (sprites/<id>.png) so the site works offline
+
+pub const STYLE_FILE: &str = "style.css";
+
+pub const STYLE: &str = "body { font-family: sans-serif; margin: 2em; background: #f4f4f4; }
+nav a { margin-right: 1em; }
+.cards { display: flex; flex-wrap: wrap; gap: 1em; }
+.card { background: white; border-radius: 8px; padding: 1em; width: 14em; box-shadow: 0 1px 3px #0003; }
+.card.shiny { border: 2px solid gold; }
+.card img { display: block; margin: auto; width: 96px; height: 96px; image-rendering: pixelated; }
+.card h2 { font-size: 1.1em; margin: 0.5em 0; text-transform: capitalize; }
+.type { display: inline-block; background: #ddd; border-radius: 4px; padding: 0 0.4em; margin-right: 0.3em; }
+.card table { width: 100%; font-size: 0.9em; }
+.card td:last-child { text-align: right; }
+";
+
+pub struct Page {
+    pub file: String,
+    pub html: String,
+}
+
+pub fn page_file(gen: &Generation) -> String {
+    format!("gen-{}.html", gen.number())
+}
+
+// Cards are in the order of pokes, images maps a species id to its image path
+pub fn pages(pokes: &[DbPoke], images: &HashMap<i64, String>) -> Vec<Page> {
+    let gens: Vec<Generation> = Generation::ALL
+        .into_iter()
+        .filter(|gen| pokes.iter().any(|poke| gen.ids().contains(&poke.id)))
+        .collect();
+    let nav = nav(pokes, &gens);
+
+    let mut pages = vec![Page {
+        file: String::from("index.html"),
+        html: page("Collection", &nav, pokes.iter(), images),
+    }];
+    for gen in gens {
+        let cards = pokes.iter().filter(|poke| gen.ids().contains(&poke.id));
+        pages.push(Page {
+            file: page_file(&gen),
+            html: page(&gen.to_string(), &nav, cards, images),
+        });
+    }
+    pages
+}
+
+fn nav(pokes: &[DbPoke], gens: &[Generation]) -> String {
+    let mut nav = String::from("<nav><a href=\"index.html\">All</a>");
+    for gen in gens {
+        let caught = pokes
+            .iter()
+            .filter(|poke| gen.ids().contains(&poke.id))
+            .count();
+        let _ = write!(
+            nav,
+            "<a href=\"{}\">{} {}/{}</a>",
+            page_file(gen),
+            escape(&gen.to_string()),
+            caught,
+            gen.total()
+        );
+    }
+    nav.push_str("</nav>");
+    nav
+}
+
+fn page<'a>(
+    title: &str,
+    nav: &str,
+    pokes: impl Iterator<Item = &'a DbPoke>,
+    images: &HashMap<i64, String>,
+) -> String {
+    let title = escape(title);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<link rel=\"stylesheet\" href=\"{}\">\n</head>\n<body>\n<h1>{}</h1>\n{}\n<div class=\"cards\">\n",
+        title, STYLE_FILE, title, nav
+    );
+    for poke in pokes {
+        html.push_str(&card(poke, images.get(&poke.id).map(String::as_str)));
+    }
+    html.push_str("</div>\n</body>\n</html>\n");
+    html
+}
+
+fn card(poke: &DbPoke, image: Option<&str>) -> String {
+    let name = escape(&poke.name);
+    let shiny = poke.shiny_count() > 0;
+    let mut card = format!(
+        "<div class=\"card{}\">\n",
+        if shiny { " shiny" } else { "" }
+    );
+
+    if let Some(image) = image {
+        let _ = writeln!(card, "<img src=\"{}\" alt=\"{}\">", escape(image), name);
+    }
+    let _ = writeln!(card, "<h2>#{} {}</h2>", poke.id, name);
+    let _ = writeln!(
+        card,
+        "<div>{}</div>",
+        poke.type_names()
+            .iter()
+            .map(|t| format!("<span class=\"type\">{}</span>", escape(t)))
+            .collect::<String>()
+    );
+
+    card.push_str("<table>\n");
+    for stat in STAT_NAMES {
+        if let Some(value) = poke.stat(stat) {
+            let _ = writeln!(card, "<tr><td>{}</td><td>{}</td></tr>", stat, value);
+        }
+    }
+    card.push_str("</table>\n");
+
+    let _ = writeln!(
+        card,
+        "<p>Caught {} ({} shiny)</p>",
+        poke.count(),
+        poke.shiny_count()
+    );
+    let nicknames: Vec<String> = poke
+        .instances
+        .iter()
+        .filter_map(|instance| instance.nickname.as_deref())
+        .map(escape)
+        .collect();
+    if !nicknames.is_empty() {
+        let _ = writeln!(card, "<p>{}</p>", nicknames.join(", "));
+    }
+    card.push_str("</div>\n");
+    card
+}
+
+// Extension of the image file name in a sprite URL, png when there is none
+// or when it could not be used in a file name
+pub fn image_extension(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let file = path.rsplit('/').next().unwrap_or_default();
+    Path::new(file)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| !ext.is_empty() && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or("png")
+        .to_lowercase()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poke::PokeInstance;
+    use chrono::Utc;
+
+    fn poke(id: i64, name: &str, shiny: bool, nickname: Option<&str>) -> DbPoke {
+        DbPoke {
+            id,
+            name: name.to_string(),
+            types: Vec::new(),
+            base_experience: 0,
+            stats: Vec::new(),
+            sprites: Default::default(),
+            instances: vec![PokeInstance {
+                id,
+                caught_at: Utc::now(),
+                nickname: nickname.map(str::to_string),
+                is_shiny: shiny,
+                shiny_source: None,
+                shiny_proof: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_pages() {
+        let pokes = vec![
+            poke(25, "pikachu", true, Some("<sparky>")),
+            poke(152, "chikorita", false, None),
+        ];
+        let images = HashMap::from([(25, String::from("sprites/25-shiny.png"))]);
+        let pages = pages(&pokes, &images);
+
+        let files: Vec<&str> = pages.iter().map(|page| page.file.as_str()).collect();
+        assert_eq!(files, ["index.html", "gen-1.html", "gen-2.html"]);
+
+        let index = &pages[0].html;
+        assert!(index.contains("<div class=\"card shiny\">"));
+        assert!(index.contains("<img src=\"sprites/25-shiny.png\" alt=\"pikachu\">"));
+        assert!(index.contains("<p>&lt;sparky&gt;</p>"));
+        assert!(index.contains("<a href=\"gen-1.html\">Gen 1 (Kanto) 1/151</a>"));
+        assert!(index.contains("chikorita"));
+
+        let kanto = &pages[1].html;
+        assert!(kanto.contains("pikachu"));
+        assert!(!kanto.contains("chikorita"));
+    }
+
+    #[test]
+    fn test_image_extension() {
+        assert_eq!(image_extension("https://host/sprites/25.png"), "png");
+        assert_eq!(image_extension("https://host/sprites/25.GIF?v=2"), "gif");
+        assert_eq!(image_extension("https://host.com/path/x"), "png");
+        assert_eq!(image_extension("https://host/sprites/25.p-g"), "png");
+    }
+}
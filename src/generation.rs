@@ -0,0 +1,172 @@
+use core::fmt;
+use std::{ops::RangeInclusive, str::FromStr};
+
+// Pokemon generations with their national dex ranges (both bounds included)
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Generation {
+    Kanto,
+    Johto,
+    Hoenn,
+    Sinnoh,
+    Unova,
+    Kalos,
+    Alola,
+    Galar,
+    Paldea,
+}
+
+const ROMAN: [&str; 9] = ["i", "ii", "iii", "iv", "v", "vi", "vii", "viii", "ix"];
+
+impl Generation {
+    pub const ALL: [Generation; 9] = [
+        Generation::Kanto,
+        Generation::Johto,
+        Generation::Hoenn,
+        Generation::Sinnoh,
+        Generation::Unova,
+        Generation::Kalos,
+        Generation::Alola,
+        Generation::Galar,
+        Generation::Paldea,
+    ];
+
+    // 1 for Kanto, 9 for Paldea
+    pub fn number(&self) -> usize {
+        *self as usize + 1
+    }
+
+    pub fn from_number(number: usize) -> Option<Generation> {
+        Generation::ALL.get(number.checked_sub(1)?).copied()
+    }
+
+    pub fn region(&self) -> &'static str {
+        match self {
+            Generation::Kanto => "Kanto",
+            Generation::Johto => "Johto",
+            Generation::Hoenn => "Hoenn",
+            Generation::Sinnoh => "Sinnoh",
+            Generation::Unova => "Unova",
+            Generation::Kalos => "Kalos",
+            Generation::Alola => "Alola",
+            Generation::Galar => "Galar",
+            Generation::Paldea => "Paldea",
+        }
+    }
+
+    pub fn roman(&self) -> &'static str {
+        ROMAN[*self as usize]
+    }
+
+    pub fn ids(&self) -> RangeInclusive<i64> {
+        match self {
+            Generation::Kanto => 1..=151,
+            Generation::Johto => 152..=251,
+            Generation::Hoenn => 252..=386,
+            Generation::Sinnoh => 387..=493,
+            Generation::Unova => 494..=649,
+            Generation::Kalos => 650..=721,
+            Generation::Alola => 722..=809,
+            Generation::Galar => 810..=905,
+            Generation::Paldea => 906..=1025,
+        }
+    }
+
+    pub fn start(&self) -> i64 {
+        *self.ids().start()
+    }
+
+    pub fn end(&self) -> i64 {
+        *self.ids().end()
+    }
+
+    pub fn total(&self) -> i64 {
+        self.end() - self.start() + 1
+    }
+
+    // Generation which introduced a national dex number
+    pub fn from_id(id: i64) -> Option<Generation> {
+        Generation::ALL
+            .into_iter()
+            .find(|gen| gen.ids().contains(&id))
+    }
+}
+
+// Accepts "3", "iii", "hoenn" (any case), also "gen3" and "generation-iii"
+impl FromStr for Generation {
+    type Err = String;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        let input = arg.trim().to_lowercase();
+        let input = input
+            .strip_prefix("generation-")
+            .or_else(|| input.strip_prefix("gen"))
+            .unwrap_or(&input)
+            .trim();
+
+        Generation::ALL
+            .into_iter()
+            .find(|gen| {
+                input == gen.number().to_string()
+                    || input == gen.roman()
+                    || input == gen.region().to_lowercase()
+            })
+            .ok_or_else(|| {
+                format!(
+                    "Unknown generation {}, use 1 to 9, i to ix or a region name",
+                    arg
+                )
+            })
+    }
+}
+
+impl fmt::Display for Generation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Gen {} ({})", self.number(), self.region())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_id() {
+        assert_eq!(Generation::from_id(0), None);
+        assert_eq!(Generation::from_id(1), Some(Generation::Kanto));
+        assert_eq!(Generation::from_id(151), Some(Generation::Kanto));
+        assert_eq!(Generation::from_id(152), Some(Generation::Johto));
+        assert_eq!(Generation::from_id(386), Some(Generation::Hoenn));
+        assert_eq!(Generation::from_id(1025), Some(Generation::Paldea));
+        assert_eq!(Generation::from_id(1026), None);
+
+        // Ranges follow each other without gaps
+        let total: i64 = Generation::ALL.iter().map(|gen| gen.total()).sum();
+        assert_eq!(total, 1025);
+    }
+
+    #[test]
+    fn test_parse() {
+        for input in [
+            "3",
+            "iii",
+            "III",
+            "hoenn",
+            "Hoenn",
+            "gen3",
+            "generation-iii",
+        ] {
+            assert_eq!(input.parse::<Generation>(), Ok(Generation::Hoenn));
+        }
+        assert_eq!("ix".parse::<Generation>(), Ok(Generation::Paldea));
+        assert!("0".parse::<Generation>().is_err());
+        assert!("10".parse::<Generation>().is_err());
+        assert!("orre".parse::<Generation>().is_err());
+        assert_eq!(
+            "gen12".parse::<Generation>(),
+            Err(String::from(
+                "Unknown generation gen12, use 1 to 9, i to ix or a region name"
+            ))
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::error::PokeError;
+
+// Pokemon files of a PokeAPI data dump, as produced by the api-data project
+// (data/api/v2/pokemon/<id>/index.json), or a flat directory of <name>.json
+// The root of the dump, its api/v2 directory or the pokemon directory can be given
+
+pub fn pokemon_files(root: &Path) -> Result<Vec<PathBuf>, PokeError> {
+    let dir = [
+        root.join("data").join("api").join("v2").join("pokemon"),
+        root.join("api").join("v2").join("pokemon"),
+        root.join("pokemon"),
+        root.to_path_buf(),
+    ]
+    .into_iter()
+    .find(|dir| dir.is_dir())
+    .ok_or_else(|| PokeError::Config(format!("{} is not a directory", root.display())))?;
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            let index = path.join("index.json");
+            if index.is_file() {
+                files.push(index);
+            }
+        // The index.json next to the pokemon directories is the list of resources
+        } else if path.extension().is_some_and(|ext| ext == "json")
+            && path.file_name().is_some_and(|name| name != "index.json")
+        {
+            files.push(path);
+        }
+    }
+
+    if files.is_empty() {
+        return Err(PokeError::Config(format!(
+            "No pokemon json files in {}",
+            dir.display()
+        )));
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pokemon_files() {
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures");
+        let files = pokemon_files(&fixtures).unwrap();
+        assert_eq!(files.len(), 5);
+        assert!(files[0].ends_with("pokemon/bulbasaur.json"));
+
+        // api-data layout
+        let dir =
+            std::env::temp_dir().join(format!("poke-collect-import-test-{}", std::process::id()));
+        let pokemon = dir.join("data").join("api").join("v2").join("pokemon");
+        fs::create_dir_all(pokemon.join("25")).unwrap();
+        fs::write(pokemon.join("25").join("index.json"), "{}").unwrap();
+        fs::write(pokemon.join("index.json"), "{}").unwrap();
+        assert_eq!(
+            pokemon_files(&dir).unwrap(),
+            [pokemon.join("25").join("index.json")]
+        );
+
+        fs::remove_dir_all(&dir).ok();
+        assert!(matches!(pokemon_files(&dir), Err(PokeError::Config(_))));
+    }
+}
@@ -1,81 +1,175 @@
 #![allow(dead_code, unused_variables)]
 
 use std::{
+    collections::{HashMap, HashSet},
     env,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
     sync::{
         atomic::{AtomicBool, Ordering},
-        mpsc, Arc,
+        Arc,
     },
-    thread,
+    time::Duration,
 };
 
-use clap::{arg, command, Arg, ArgMatches, Command};
+use clap::{arg, command, Arg, ArgAction, ArgMatches, Command};
 use dotenv::dotenv;
-use reqwest::{Client, ClientBuilder};
 use serde_json::{from_value, Value};
-use sha2::{Digest, Sha256};
 use sqlx::{migrate, postgres::PgRow, Pool, Postgres, Row};
 
+mod api;
+mod cache;
+mod catch;
+mod dex;
+mod error;
+mod gallery;
+mod generation;
+mod import;
+#[cfg(test)]
+mod mock_api;
+mod output;
 mod poke;
+mod refresh;
+mod shiny;
 mod sprite;
-use poke::{DbPoke, Pokemon, PokemonType, Stat};
-use tokio::task;
-
-const GEN1: std::ops::Range<i32> = 1..151;
-const GEN2: std::ops::Range<i32> = 152..251;
-const GEN3: std::ops::Range<i32> = 252..386;
-const GEN4: std::ops::Range<i32> = 387..493;
-const GEN5: std::ops::Range<i32> = 494..649;
-const GEN6: std::ops::Range<i32> = 650..721;
-const GEN7: std::ops::Range<i32> = 722..809;
-const GEN8: std::ops::Range<i32> = 810..905;
-const GEN9: std::ops::Range<i32> = 906..1025;
+mod target;
+mod terminal;
+mod throttle;
+mod trainer;
+use api::PokeApi;
+use cache::ResponseCache;
+use catch::{CatchOutcome, CatchReport, DEFAULT_CONCURRENCY};
+use dex::DexGeneration;
+use error::PokeError;
+use generation::Generation;
+use output::OutputFormat;
+use poke::{CollectionEntry, DbPoke, PokeInstance, Pokemon, PokemonType, Stat};
+use refresh::{RefreshReport, RefreshStatus};
+use shiny::{
+    HuntIdentity, HuntState, MineOptions, ShinyCheck, ShinyEncounter, ShinyProof, ShinySource,
+};
+use sprite::{DbSprites, SpriteInfo, SpriteQuery, SpriteVersion};
+use target::Target;
+use terminal::ColorMode;
+use tokio::{sync::Semaphore, task};
+use trainer::{Trainer, DEFAULT_TRAINER};
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+async fn run() -> Result<(), PokeError> {
     // Init dotenv
     dotenv().ok();
 
-    // Setup reqwest client for API queries
-    let client = ClientBuilder::new()
-        .connect_timeout(std::time::Duration::from_secs(10))
-        .build()
-        .unwrap_or_default();
-
-    // Setup postgres DB
-    let db_url = env::var("DB_URL")?;
-
-    let db_pool = sqlx::postgres::PgPool::connect(&db_url).await?;
-
-    migrate!("./migrations").run(&db_pool).await?;
-
     // Setup clap CLI commands
     let cli_result: ArgMatches = command!()
         .subcommand_required(true)
         .arg_required_else_help(true)
+        .arg(arg!(--offline "Only use cached API responses").global(true))
+        .arg(
+            arg!(--trainer <NAME> "Trainer whose collection is used")
+                .env("POKE_TRAINER")
+                .default_value(DEFAULT_TRAINER)
+                .global(true),
+        )
+        .arg(
+            arg!(--output <FORMAT> "Output format")
+                .value_parser(clap::value_parser!(OutputFormat))
+                .default_value("text")
+                .global(true),
+        )
         .subcommand(
             Command::new("catch")
                 .about("Catch a pokemon")
-                .arg(arg!(<POKE> "pokemon name").required(true)),
+                .arg(arg!(<POKE> "pokemon name or dex number").required(true))
+                .arg(arg!(--refresh "Refresh data from the API if already caught"))
+                .arg(arg!(--again "Catch another one even if already caught"))
+                .arg(arg!(--nickname <NICKNAME> "Nickname of the caught pokemon"))
+                .arg(arg!(--"store-sprites" "Keep the sprite images in the database"))
+                .args(encounter_args()),
         )
         .subcommand(
             Command::new("info")
                 .about("Get info on a pokemon")
-                .arg(arg!(<POKE> "pokemon name").required(true)),
+                .arg(arg!(<POKE> "pokemon name").required(true))
+                .arg(arg!(--sprite "Draw the sprite in the terminal (text output only)")),
         )
         .subcommand(
             Command::new("shiny")
                 .about("Catch a shiny version")
+                .args_conflicts_with_subcommands(true)
+                .subcommand_negates_reqs(true)
+                .subcommand(
+                    Command::new("bench")
+                        .about("Measure the shiny hash rate")
+                        .arg(
+                            arg!(--threads <N> "Number of mining threads (default: available parallelism)")
+                                .value_parser(clap::value_parser!(u32).range(1..)),
+                        )
+                        .arg(
+                            arg!(--seconds <SECONDS> "Duration of the bench")
+                                .value_parser(clap::value_parser!(u64).range(1..))
+                                .default_value("5"),
+                        ),
+                )
+                .arg(arg!(<POKE> "pokemon name").required(true))
+                .arg(
+                    arg!(--instance <ID> "Which caught pokemon (default: oldest non shiny one)")
+                        .value_parser(clap::value_parser!(i64)),
+                )
+                .arg(
+                    arg!(<TARGET> "prefix:<hex>, suffix:<hex>, below:<difficulty>, or a number of leading <DIGIT>")
+                        .value_parser(parse_target_arg)
+                        .required(false)
+                        .required_unless_present("resume"),
+                )
+                .arg(
+                    arg!([DIGIT] "Hex digit repeated <TARGET> times at the start of the hash")
+                        .value_parser(parse_hex_digit),
+                )
+                .arg(
+                    arg!(--resume "Resume the saved hunt of this pokemon (with its thread count)")
+                        .conflicts_with_all(["TARGET", "DIGIT", "threads"]),
+                )
+                .arg(
+                    arg!(--threads <N> "Number of mining threads (default: available parallelism)")
+                        .value_parser(clap::value_parser!(u32).range(1..)),
+                )
+                .arg(
+                    arg!(--timeout <SECONDS> "Give up after this time, the pokemon is left unchanged")
+                        .value_parser(clap::value_parser!(u64)),
+                ),
+        )
+        .subcommand(
+            Command::new("sprite")
+                .about("Draw or download a sprite of a pokemon")
                 .arg(arg!(<POKE> "pokemon name").required(true))
                 .arg(
-                    arg!(<DIFFICULTY> "Number of leading <NUMBER>")
-                        .required(true)
-                        .value_parser(parse_difficulty_and_number),
+                    arg!(--version <VERSION> "Game version of the sprite (default: main sprite)")
+                        .value_parser(clap::value_parser!(SpriteVersion)),
                 )
+                .arg(arg!(--back "Back sprite"))
+                .arg(arg!(--shiny "Shiny sprite"))
+                .arg(arg!(--female "Female sprite"))
+                .arg(arg!(--save <FILE> "Write the image to a file instead of drawing it")),
+        )
+        .subcommand(
+            Command::new("verify-shiny")
+                .about("Check the proof of shiny pokemon")
+                .arg(arg!(<POKE> "pokemon name").required(true))
                 .arg(
-                    arg!(<NUMBER> "Which number you want for the hash")
-                        .required(true)
-                        .value_parser(parse_difficulty_and_number),
+                    arg!(--instance <ID> "Which caught pokemon (default: every shiny one)")
+                        .value_parser(clap::value_parser!(i64)),
                 ),
         )
         .subcommand(
@@ -84,302 +178,1327 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .arg(
                     arg!(<GEN> "specify gen")
                         .required(false)
-                        .value_parser(parse_generation),
+                        .value_parser(Generation::from_str),
                 ),
         )
+        .subcommand(
+            Command::new("import-dex")
+                .about("Import pokemon data from a PokeAPI json dump, to catch without the API")
+                .arg(arg!(<PATH> "directory of the dump (api-data format)").required(true)),
+        )
+        .subcommand(
+            Command::new("refresh")
+                .about("Update the stored data of your collection when it changed on the API"),
+        )
+        .subcommand(
+            Command::new("export-gallery")
+                .about("Write a static HTML gallery of your collection")
+                .arg(arg!(<DIR> "directory of the website").required(true)),
+        )
         .subcommand(
             Command::new("multi-catch")
                 .about("Catch multiple pokemon")
-                .arg(Arg::new("names").num_args(1..).required(true)),
+                .arg(
+                    Arg::new("names")
+                        .help("pokemon names, dex numbers or dex ranges (1-151)")
+                        .num_args(1..)
+                        .required_unless_present("gen"),
+                )
+                .arg(
+                    arg!(--gen <GEN> "Catch every pokemon of a generation (repeatable)")
+                        .value_parser(Generation::from_str)
+                        .action(ArgAction::Append),
+                )
+                .arg(arg!(--refresh "Refresh data from the API if already caught"))
+                .arg(arg!(--again "Catch another one even if already caught"))
+                .arg(arg!(--"store-sprites" "Keep the sprite images in the database"))
+                .arg(
+                    arg!(--concurrency <N> "Number of pokemon caught at the same time (default: 4)")
+                        .value_parser(clap::value_parser!(u32).range(1..)),
+                )
+                .args(encounter_args()),
+        )
+        .subcommand(
+            Command::new("dex")
+                .about("Show pokedex completion per generation")
+                .arg(
+                    arg!(<GEN> "specify gen")
+                        .required(false)
+                        .value_parser(Generation::from_str),
+                )
+                .arg(arg!(--missing "List the pokemon not caught yet")),
+        )
+        .subcommand(
+            Command::new("trainer")
+                .about("Manage trainers")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("create")
+                        .about("Create a trainer")
+                        .arg(arg!(<NAME> "trainer name").required(true)),
+                )
+                .subcommand(Command::new("list").about("List trainers"))
+                .subcommand(
+                    Command::new("delete")
+                        .about("Delete a trainer and its collection")
+                        .arg(arg!(<NAME> "trainer name").required(true)),
+                ),
         )
         .get_matches();
+    let output = *cli_result.get_one::<OutputFormat>("output").unwrap();
+
+    // Bench does not need the DB
+    if let Some(("shiny", sub_matches)) = cli_result.subcommand() {
+        if let Some(("bench", sub_matches)) = sub_matches.subcommand() {
+            let threads = sub_matches
+                .get_one::<u32>("threads")
+                .map(|threads| *threads as usize)
+                .unwrap_or_else(shiny::default_threads);
+            let seconds = *sub_matches.get_one::<u64>("seconds").unwrap();
+            let bench =
+                task::spawn_blocking(move || shiny::bench(threads, Duration::from_secs(seconds)))
+                    .await??;
+            return output::print_one(output, &bench);
+        }
+    }
+
+    // The <count> <digit> form of a shiny target is checked before the DB is opened
+    let shiny_target = match cli_result.subcommand() {
+        Some(("shiny", sub_matches)) if !sub_matches.get_flag("resume") => Some(
+            parse_target(
+                sub_matches.get_one::<String>("TARGET").unwrap(),
+                sub_matches.get_one::<u8>("DIGIT").copied(),
+            )
+            .map_err(PokeError::Config)?,
+        ),
+        _ => None,
+    };
+    // Same for the dex ranges of multi catch
+    let multi_catch_names = match cli_result.subcommand() {
+        Some(("multi-catch", sub_matches)) => {
+            let inputs: Vec<String> = sub_matches
+                .get_many::<String>("names")
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect();
+            let mut names = catch::expand_names(&inputs)?;
+            for gen in sub_matches
+                .get_many::<Generation>("gen")
+                .into_iter()
+                .flatten()
+            {
+                names.extend(gen.ids().map(|id| id.to_string()));
+            }
+            names
+        }
+        _ => Vec::new(),
+    };
+
+    // Setup postgres DB
+    let db_url =
+        env::var("DB_URL").map_err(|_| PokeError::Config("DB_URL must be set".to_string()))?;
+
+    let db_pool = sqlx::postgres::PgPool::connect(&db_url).await?;
+
+    migrate!("./migrations").run(&db_pool).await?;
+
+    // Setup reqwest client for API queries, responses are cached on disk
+    let api = PokeApi::from_env(PokeApi::default_client())
+        .with_cache(ResponseCache::from_env())
+        .offline(cli_result.get_flag("offline"));
+
+    // Trainer commands do not work on a collection
+    if let Some(("trainer", sub_matches)) = cli_result.subcommand() {
+        return match sub_matches.subcommand() {
+            Some(("create", sub_matches)) => {
+                create_trainer(sub_matches.get_one::<String>("NAME").unwrap(), &db_pool).await?;
+                Ok(())
+            }
+            Some(("list", _)) => output::print(output, &list_trainers(&db_pool).await?),
+            Some(("delete", sub_matches)) => {
+                delete_trainer(sub_matches.get_one::<String>("NAME").unwrap(), &db_pool).await
+            }
+            _ => unreachable!(),
+        };
+    }
+
+    let trainer_id = trainer_id(cli_result.get_one::<String>("trainer").unwrap(), &db_pool).await?;
 
     match cli_result.subcommand() {
         Some(("catch", sub_matches)) => {
-            catch_pokemon(
-                client,
+            let options = CatchOptions {
+                refresh: sub_matches.get_flag("refresh"),
+                again: sub_matches.get_flag("again"),
+                store_sprites: sub_matches.get_flag("store-sprites"),
+                nickname: sub_matches.get_one::<String>("nickname").cloned(),
+                encounter: encounter_from_matches(sub_matches),
+            };
+            let outcome = CatchOutcome(
+                catch_pokemon(
+                    &api,
+                    sub_matches.get_one::<String>("POKE").unwrap(),
+                    &options,
+                    trainer_id,
+                    &db_pool,
+                )
+                .await?,
+            );
+            output::print_one(output, &outcome)?;
+            let poke = outcome.0;
+
+            // Only for a human looking at a terminal, a missing sprite is not an error
+            if output == OutputFormat::Text && io::stdout().is_terminal() {
+                let shiny = poke.instances.iter().any(|i| i.is_shiny);
+                if let Err(e) = show_sprite(&api, &poke, shiny, &db_pool).await {
+                    eprintln!("Could not draw the sprite of {}: {}", poke.name, e);
+                }
+            }
+        }
+        Some(("info", sub_matches)) => {
+            let poke = info_pokemon(
                 sub_matches.get_one::<String>("POKE").unwrap(),
+                trainer_id,
                 &db_pool,
             )
             .await?;
-        }
-        Some(("info", sub_matches)) => {
-            info_pokemon(sub_matches.get_one::<String>("POKE").unwrap(), &db_pool).await?;
+            output::print_one(output, &poke)?;
+
+            if sub_matches.get_flag("sprite") && output == OutputFormat::Text {
+                let shiny = poke.instances.iter().any(|i| i.is_shiny);
+                show_sprite(&api, &poke, shiny, &db_pool).await?;
+            }
         }
         Some(("shiny", sub_matches)) => {
+            let name = sub_matches.get_one::<String>("POKE").unwrap();
+            let options = MineOptions {
+                threads: sub_matches
+                    .get_one::<u32>("threads")
+                    .map(|threads| *threads as usize)
+                    .unwrap_or_else(shiny::default_threads),
+                timeout: sub_matches
+                    .get_one::<u64>("timeout")
+                    .map(|secs| Duration::from_secs(*secs)),
+                progress: true,
+                ..Default::default()
+            };
             shiny_pokemon(
-                sub_matches.get_one::<String>("POKE").unwrap(),
-                *sub_matches.get_one::<usize>("DIFFICULTY").unwrap(),
-                *sub_matches.get_one::<usize>("NUMBER").unwrap(),
+                name,
+                sub_matches.get_one::<i64>("instance").copied(),
+                shiny_target,
+                &options,
+                trainer_id,
+                &db_pool,
+            )
+            .await?;
+            // Text only gets the status message, others the updated pokemon
+            if output != OutputFormat::Text {
+                output::print_one(output, &info_pokemon(name, trainer_id, &db_pool).await?)?;
+            }
+        }
+        Some(("sprite", sub_matches)) => {
+            let name = sub_matches.get_one::<String>("POKE").unwrap();
+            let version = sub_matches.get_one::<SpriteVersion>("version").copied();
+            let query = SpriteQuery {
+                back: sub_matches.get_flag("back"),
+                shiny: sub_matches.get_flag("shiny"),
+                female: sub_matches.get_flag("female"),
+            };
+            let sprite = find_sprite(&api, name, version, query, &db_pool).await?;
+            let save = sub_matches.get_one::<String>("save");
+
+            if output != OutputFormat::Text {
+                output::print_one(output, &sprite)?;
+            }
+            if let Some(path) = save {
+                tokio::fs::write(path, load_sprite(&api, &sprite.url, &db_pool).await?).await?;
+                eprintln!("Sprite saved to {}", path);
+            } else if output == OutputFormat::Text {
+                let bytes = load_sprite(&api, &sprite.url, &db_pool).await?;
+                println!("{}", terminal::render_sprite(&bytes, ColorMode::detect())?);
+            }
+        }
+        Some(("verify-shiny", sub_matches)) => {
+            let name = sub_matches.get_one::<String>("POKE").unwrap();
+            let checks = verify_shiny(
+                name,
+                sub_matches.get_one::<i64>("instance").copied(),
+                trainer_id,
                 &db_pool,
             )
             .await?;
+            output::print(output, &checks)?;
+            if checks.iter().any(|check| !check.valid) {
+                return Err(PokeError::InvalidShinyProof(name.to_string()));
+            }
         }
         Some(("collection", sub_matches)) => {
-            collection_pokemon(sub_matches.get_one::<usize>("GEN"), &db_pool).await?;
+            let pokes = collection_pokemon(
+                sub_matches.get_one::<Generation>("GEN"),
+                trainer_id,
+                &db_pool,
+            )
+            .await?;
+
+            let entries: Vec<CollectionEntry> = pokes.into_iter().map(CollectionEntry).collect();
+            output::print(output, &entries)?;
+        }
+        Some(("import-dex", sub_matches)) => {
+            let path = PathBuf::from(sub_matches.get_one::<String>("PATH").unwrap());
+            import_dex(&path, &db_pool).await?;
+        }
+        Some(("refresh", _)) => {
+            let reports = refresh_collection(&api, trainer_id, &db_pool).await?;
+            output::print(output, &reports)?;
+            eprintln!("{}", refresh::summary(&reports));
+
+            let failed = reports
+                .iter()
+                .filter(|r| r.status == RefreshStatus::Failed)
+                .count();
+            if failed > 0 {
+                return Err(PokeError::RefreshFailed {
+                    failed,
+                    total: reports.len(),
+                });
+            }
+        }
+        Some(("export-gallery", sub_matches)) => {
+            let dir = PathBuf::from(sub_matches.get_one::<String>("DIR").unwrap());
+            export_gallery(&api, &dir, trainer_id, &db_pool).await?;
+            println!("{}", dir.join("index.html").display());
         }
         Some(("multi-catch", sub_matches)) => {
-            if let Some(names) = sub_matches.get_many::<String>("names") {
-                let names: Vec<String> = names.map(|name| name.to_string()).collect();
-                multi_catch_pokemon(client, names, &db_pool).await?;
+            let options = CatchOptions {
+                refresh: sub_matches.get_flag("refresh"),
+                again: sub_matches.get_flag("again"),
+                store_sprites: sub_matches.get_flag("store-sprites"),
+                nickname: None,
+                encounter: encounter_from_matches(sub_matches),
+            };
+            let concurrency = sub_matches
+                .get_one::<u32>("concurrency")
+                .map_or(DEFAULT_CONCURRENCY, |n| *n as usize);
+            let reports = multi_catch_pokemon(
+                &api,
+                multi_catch_names,
+                &options,
+                concurrency,
+                trainer_id,
+                &db_pool,
+            )
+            .await?;
+            output::print(output, &reports)?;
+            eprintln!("{}", catch::summary(&reports));
+
+            let failed = reports.iter().filter(|r| r.status.is_failure()).count();
+            if failed > 0 {
+                return Err(PokeError::CatchFailed {
+                    failed,
+                    total: reports.len(),
+                });
             }
         }
+        Some(("dex", sub_matches)) => {
+            let dex = dex_pokemon(
+                &api,
+                sub_matches.get_one::<Generation>("GEN"),
+                sub_matches.get_flag("missing"),
+                trainer_id,
+                &db_pool,
+            )
+            .await?;
+            output::print(output, &dex)?;
+        }
         _ => unreachable!(),
     }
 
     Ok(())
 }
 
+// What catch does when the pokemon is already in the collection
+// refresh: update species data from the API (caught instances are kept)
+// again: catch one more instance of the species
+// store_sprites: also keep the sprite images in the database
+// encounter: roll for a shiny on each new instance
+#[derive(Default, Clone)]
+struct CatchOptions {
+    refresh: bool,
+    again: bool,
+    store_sprites: bool,
+    nickname: Option<String>,
+    encounter: Option<ShinyEncounter>,
+}
+
+fn encounter_args() -> [Arg; 3] {
+    [
+        arg!(--"shiny-odds" [N] "Roll for a shiny with a 1 in N chance (default: 4096)")
+            .value_parser(clap::value_parser!(u32).range(1..))
+            .default_missing_value("4096"),
+        arg!(--"shiny-charm" "Shiny charm, 3 shiny rolls instead of 1"),
+        arg!(--seed <SEED> "Seed of the shiny rolls, to replay the same catches (rolls at the default odds)")
+            .value_parser(clap::value_parser!(u64)),
+    ]
+}
+
+// No roll unless odds, the charm or a seed are given
+fn encounter_from_matches(matches: &ArgMatches) -> Option<ShinyEncounter> {
+    let odds = matches.get_one::<u32>("shiny-odds").copied();
+    let charm = matches.get_flag("shiny-charm");
+    let seed = matches.get_one::<u64>("seed").copied();
+    if odds.is_none() && !charm && seed.is_none() {
+        return None;
+    }
+    Some(ShinyEncounter::new(
+        odds.unwrap_or(shiny::DEFAULT_SHINY_ODDS),
+        charm,
+        seed,
+    ))
+}
+
 async fn catch_pokemon(
-    client: Client,
-    name: &String,
+    api: &PokeApi,
+    name: &str,
+    options: &CatchOptions,
+    trainer_id: i64,
     db_co: &Pool<Postgres>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let rep = client
-        .get(format!("{}{}", "https://pokeapi.co/api/v2/pokemon/", name))
-        .send()
+) -> Result<DbPoke, PokeError> {
+    // Check before querying the API, name can also be the pokedex id
+    let name = &catch::normalize_name(name);
+    let db_select = "SELECT poke_name FROM poke JOIN poke_instance USING (poke_id) WHERE (poke_name=$1 OR poke_id::text=$1) AND trainer_id=$2 LIMIT 1";
+    let caught = sqlx::query(db_select)
+        .bind(name)
+        .bind(trainer_id)
+        .fetch_optional(db_co)
         .await?;
+    if let (Some(row), false, false) = (&caught, options.refresh, options.again) {
+        return Err(PokeError::AlreadyCaught(row.try_get("poke_name")?));
+    }
 
-    if rep.status().is_success() {
-        let poke: Pokemon = rep.json().await?;
-        println!("{}", poke);
+    // Refresh is about the API data, the imported dex is skipped
+    let body = if options.refresh {
+        api.fetch_pokemon(name).await?
+    } else {
+        pokemon_data(api, name, db_co).await?
+    };
+    let poke: Pokemon = serde_json::from_slice(&body)?;
 
-        // DB insertion
-        // Transform into json stats and types directly in query
-        let db_insert = "INSERT INTO poke (poke_id, poke_name, poke_type, poke_base_experience, poke_stats) VALUES ($1, $2, $3::json, $4, $5::json)";
+    // DB insertion
+    // Transform into json stats and types directly in query
+    let db_insert = if options.refresh {
+        "INSERT INTO poke (poke_id, poke_name, poke_type, poke_base_experience, poke_stats, poke_sprites) VALUES ($1, $2, $3::json, $4, $5::json, $6::json)
+        ON CONFLICT (poke_id) DO UPDATE SET poke_name=EXCLUDED.poke_name, poke_type=EXCLUDED.poke_type, poke_base_experience=EXCLUDED.poke_base_experience, poke_stats=EXCLUDED.poke_stats, poke_sprites=EXCLUDED.poke_sprites"
+    } else {
+        "INSERT INTO poke (poke_id, poke_name, poke_type, poke_base_experience, poke_stats, poke_sprites) VALUES ($1, $2, $3::json, $4, $5::json, $6::json)
+        ON CONFLICT (poke_id) DO NOTHING"
+    };
 
-        // Optionnal DbPoke into (just to use it)
-        let db_poke: DbPoke = poke.into();
+    // Instances only hold the one caught by this call
+    let mut db_poke: DbPoke = poke.into();
 
-        let stats_json = serde_json::to_string(&db_poke.stats)?;
-        let types_json = serde_json::to_string(&db_poke.types)?;
-        sqlx::query(db_insert)
-            .bind(db_poke.id)
-            .bind(db_poke.name)
-            .bind(types_json)
-            .bind(db_poke.base_experience)
-            .bind(stats_json)
-            .execute(db_co)
-            .await?;
-        Ok(())
-    } else {
-        // Manual way to handling errors, could use anyhow or thiserror
-        Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("API Response error: {}", rep.status()),
-        )))
+    let stats_json = serde_json::to_string(&db_poke.stats)?;
+    let types_json = serde_json::to_string(&db_poke.types)?;
+    let sprites_json = serde_json::to_string(&db_poke.sprites)?;
+
+    if options.store_sprites {
+        store_sprites(api, &db_poke.sprites, db_co).await;
     }
-}
 
-async fn info_pokemon(
-    name: &String,
-    db_co: &Pool<Postgres>,
-) -> Result<DbPoke, Box<dyn std::error::Error>> {
-    let db_select = "SELECT * FROM poke WHERE poke_name=$1";
-    let row = sqlx::query(db_select).bind(name).fetch_one(db_co).await?;
+    // Species and instance are inserted together
+    let mut db_tx = db_co.begin().await?;
+    sqlx::query(db_insert)
+        .bind(db_poke.id)
+        .bind(&db_poke.name)
+        .bind(types_json)
+        .bind(db_poke.base_experience)
+        .bind(stats_json)
+        .bind(sprites_json)
+        .execute(&mut *db_tx)
+        .await?;
+
+    if caught.is_some() && !options.again {
+        db_tx.commit().await?;
+        eprintln!("{} refreshed", db_poke.name);
+        return Ok(db_poke);
+    }
+
+    let shiny = options
+        .encounter
+        .as_ref()
+        .is_some_and(|encounter| encounter.roll());
+    let db_insert_instance = "INSERT INTO poke_instance (poke_id, trainer_id, instance_nickname, instance_is_shiny, instance_shiny_source) VALUES ($1, $2, $3, $4, $5) RETURNING *";
+    let row = sqlx::query(db_insert_instance)
+        .bind(db_poke.id)
+        .bind(trainer_id)
+        .bind(&options.nickname)
+        .bind(shiny)
+        .bind(shiny.then_some(ShinySource::Encounter.as_str()))
+        .fetch_one(&mut *db_tx)
+        .await?;
+    db_tx.commit().await?;
+
+    let instance = instance_from_row(&row)?;
+    if shiny {
+        eprintln!("A shiny {} appeared!", db_poke.name);
+    }
+    eprintln!("{} caught (#{})", db_poke.name, instance.id);
+    db_poke.instances.push(instance);
+    Ok(db_poke)
+}
 
+// Species columns of the poke table, without instances
+fn poke_from_row(row: &PgRow) -> Result<DbPoke, PokeError> {
     let stats_value: Value = row.try_get("poke_stats")?;
     let stats: Vec<Stat> = from_value(stats_value)?;
     let types_value: Value = row.try_get("poke_type")?;
     let types: Vec<PokemonType> = from_value(types_value)?;
+    // Rows caught before sprites were stored have none until refreshed
+    let sprites_value: Option<Value> = row.try_get("poke_sprites")?;
+    let sprites = sprites_value.map(from_value).transpose()?;
 
-    let pokemon = DbPoke {
+    Ok(DbPoke {
         id: row.try_get("poke_id")?,
         name: row.try_get("poke_name")?,
         types,
         base_experience: row.try_get("poke_base_experience")?,
         stats,
-        is_shiny: row.try_get("poke_is_shiny")?,
-    };
+        sprites: sprites.unwrap_or_default(),
+        instances: Vec::new(),
+    })
+}
+
+fn instance_from_row(row: &PgRow) -> Result<PokeInstance, PokeError> {
+    Ok(PokeInstance {
+        id: row.try_get("instance_id")?,
+        caught_at: row.try_get("instance_caught_at")?,
+        nickname: row.try_get("instance_nickname")?,
+        is_shiny: row.try_get("instance_is_shiny")?,
+        shiny_source: row
+            .try_get::<Option<String>, _>("instance_shiny_source")?
+            .map(|source| source.parse())
+            .transpose()
+            .map_err(PokeError::ShinyHunt)?,
+        shiny_proof: proof_from_row(row)?,
+    })
+}
 
-    println!("{}", pokemon);
+fn proof_from_row(row: &PgRow) -> Result<Option<ShinyProof>, PokeError> {
+    let nonce: Option<i64> = row.try_get("instance_shiny_nonce")?;
+    let target: Option<String> = row.try_get("instance_shiny_target")?;
 
-    Ok(pokemon)
+    Ok(match (nonce, target) {
+        (Some(nonce), Some(target)) => Some(ShinyProof {
+            nonce: nonce as u64,
+            target: target.parse().map_err(PokeError::InvalidShinyProof)?,
+        }),
+        _ => None,
+    })
 }
 
-async fn shiny_pokemon(
-    name: &String,
-    difficulty: usize,
-    number: usize,
+// Rows of poke joined with poke_instance, ordered by poke_id
+fn pokes_from_rows(rows: &[PgRow]) -> Result<Vec<DbPoke>, PokeError> {
+    let mut pokes: Vec<DbPoke> = Vec::new();
+    for row in rows {
+        let poke_id: i64 = row.try_get("poke_id")?;
+        match pokes.last_mut() {
+            Some(poke) if poke.id == poke_id => (),
+            _ => pokes.push(poke_from_row(row)?),
+        }
+        if let Some(poke) = pokes.last_mut() {
+            poke.instances.push(instance_from_row(row)?);
+        }
+    }
+    Ok(pokes)
+}
+
+// Sprite URL comes from the pokemon data, both are served from the cache when possible
+async fn find_sprite(
+    api: &PokeApi,
+    name: &str,
+    version: Option<SpriteVersion>,
+    query: SpriteQuery,
     db_co: &Pool<Postgres>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let db_select = "SELECT * FROM poke WHERE poke_name=$1";
-    let poke = sqlx::query(db_select).bind(name).fetch_one(db_co).await?;
-
-    let (tx_result, rx_result) = mpsc::channel();
-    let mut handles = vec![];
-    let solution_found = Arc::new(AtomicBool::new(false));
-
-    for i in 0..8 {
-        let tx_result = tx_result.clone();
-        let solution_found = solution_found.clone();
-        let handle = thread::spawn(move || {
-            let mut counter = i as u64;
-            loop {
-                let hash = generate_hash(counter);
-                if is_shiny(&hash, &difficulty, number) {
-                    solution_found.store(true, Ordering::SeqCst);
-                    tx_result
-                        .send(counter)
-                        .expect("Could not send result for shiny hunt");
-                    return;
-                }
-                counter += 8;
+) -> Result<SpriteInfo, PokeError> {
+    let body = pokemon_data(api, name, db_co).await?;
+    let poke: Pokemon = serde_json::from_slice(&body)?;
+    let sprite = poke.sprites().find(version, query).ok_or_else(|| {
+        let version = version.map_or(String::new(), |version| format!("{} ", version));
+        PokeError::NotFound(format!("{}sprite of {}", version, name))
+    })?;
 
-                if solution_found.load(Ordering::SeqCst) {
-                    break;
-                }
-            }
-        });
-        handles.push(handle);
+    if sprite.query != query {
+        eprintln!(
+            "No such sprite for {}, using {}{}{}",
+            name,
+            if sprite.query.back { "back" } else { "front" },
+            if sprite.query.shiny { " shiny" } else { "" },
+            if sprite.query.female { " female" } else { "" },
+        );
     }
+    Ok(SpriteInfo::new(name, version, &sprite))
+}
 
-    drop(tx_result);
+// Image stored with catch --store-sprites, or from the API (and its cache)
+async fn load_sprite(
+    api: &PokeApi,
+    url: &str,
+    db_co: &Pool<Postgres>,
+) -> Result<Vec<u8>, PokeError> {
+    let db_select = "SELECT image_bytes FROM sprite_image WHERE image_url=$1";
+    let row = sqlx::query(db_select)
+        .bind(url)
+        .fetch_optional(db_co)
+        .await?;
+    match row {
+        Some(row) => Ok(row.try_get("image_bytes")?),
+        None => api.fetch_url(url).await,
+    }
+}
 
-    let result = rx_result.recv()?;
-    println!("Shiny found with : {}", result);
-    for handle in handles {
-        handle.join().expect("Could not join thread");
+// A missing image should not fail the catch, it is still drawn from the API later
+async fn store_sprites(api: &PokeApi, sprites: &DbSprites, db_co: &Pool<Postgres>) {
+    let db_insert = "INSERT INTO sprite_image (image_url, image_bytes) VALUES ($1, $2) ON CONFLICT (image_url) DO UPDATE SET image_bytes=EXCLUDED.image_bytes, image_fetched_at=now()";
+    for url in sprites.urls() {
+        let res = match api.fetch_url(url).await {
+            Ok(bytes) => sqlx::query(db_insert)
+                .bind(url)
+                .bind(bytes)
+                .execute(db_co)
+                .await
+                .map(|_| ())
+                .map_err(PokeError::from),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            eprintln!("Could not store sprite {}: {}", url, e);
+        }
     }
-    let db_upadte = "UPDATE poke SET poke_is_shiny=true where poke_name=$1";
-    sqlx::query(db_upadte).bind(name).execute(db_co).await?;
+}
+
+// Stored URL first, species caught before sprites were stored ask the API
+async fn show_sprite(
+    api: &PokeApi,
+    poke: &DbPoke,
+    shiny: bool,
+    db_co: &Pool<Postgres>,
+) -> Result<(), PokeError> {
+    let url = match poke.sprites.front(shiny) {
+        Some(url) => url.to_string(),
+        None => {
+            find_sprite(api, &poke.name, None, SpriteQuery::front(shiny), db_co)
+                .await?
+                .url
+        }
+    };
+    let bytes = load_sprite(api, &url, db_co).await?;
+    println!("{}", terminal::render_sprite(&bytes, ColorMode::detect())?);
     Ok(())
 }
 
-async fn collection_pokemon(
-    gen: Option<&usize>,
+async fn info_pokemon(
+    name: &str,
+    trainer_id: i64,
     db_co: &Pool<Postgres>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let db_select: &str;
-    let rows: Vec<PgRow>;
+) -> Result<DbPoke, PokeError> {
+    // Name can also be the pokedex id, like catch
+    let name = catch::normalize_name(name);
+    let db_select = "SELECT * FROM poke JOIN poke_instance USING (poke_id) WHERE (poke_name=$1 OR poke_id::text=$1) AND trainer_id=$2 ORDER BY instance_id";
+    let rows = sqlx::query(db_select)
+        .bind(&name)
+        .bind(trainer_id)
+        .fetch_all(db_co)
+        .await?;
 
-    if let Some(gen) = gen {
-        let start;
-        let end = match gen {
-            1 => {
-                start = GEN1.start;
-                GEN1.end
-            }
-            2 => {
-                start = GEN2.start;
-                GEN2.end
-            }
-            3 => {
-                start = GEN3.start;
-                GEN3.end
-            }
-            4 => {
-                start = GEN4.start;
-                GEN4.end
-            }
-            5 => {
-                start = GEN5.start;
-                GEN5.end
-            }
-            6 => {
-                start = GEN6.start;
-                GEN6.end
-            }
-            7 => {
-                start = GEN7.start;
-                GEN7.end
-            }
-            8 => {
-                start = GEN8.start;
-                GEN8.end
-            }
-            9 => {
-                start = GEN9.start;
-                GEN9.end
-            }
-            _ => unreachable!(),
-        };
+    if let Some(pokemon) = pokes_from_rows(&rows)?.pop() {
+        return Ok(pokemon);
+    }
 
-        db_select = "SELECT poke_name FROM poke WHERE poke_id BETWEEN $1 AND $2";
-        rows = sqlx::query(db_select)
-            .bind(start)
-            .bind(end)
-            .fetch_all(db_co)
-            .await?;
-    } else {
-        db_select = "SELECT * FROM poke";
-        rows = sqlx::query(db_select).fetch_all(db_co).await?;
+    // Not caught yet, species data without instances from the imported dex
+    let db_select =
+        "SELECT dex_data::text AS data FROM dex_entry WHERE dex_name=$1 OR dex_id::text=$1";
+    let row = sqlx::query(db_select)
+        .bind(&name)
+        .fetch_optional(db_co)
+        .await?
+        .ok_or_else(|| PokeError::NotInCollection(name.to_string()))?;
+    let poke: Pokemon = serde_json::from_str(row.try_get("data")?)?;
+    Ok(poke.into())
+}
+
+// Pokemon json from the imported dex when it is there, from the API otherwise
+async fn pokemon_data(
+    api: &PokeApi,
+    name: &str,
+    db_co: &Pool<Postgres>,
+) -> Result<Vec<u8>, PokeError> {
+    let db_select =
+        "SELECT dex_data::text AS data FROM dex_entry WHERE dex_name=$1 OR dex_id::text=$1";
+    let row = sqlx::query(db_select)
+        .bind(catch::normalize_name(name))
+        .fetch_optional(db_co)
+        .await?;
+    match row {
+        Some(row) => Ok(row.try_get::<String, _>("data")?.into_bytes()),
+        None => api.fetch_pokemon(name).await,
     }
+}
 
-    for row in rows.iter() {
-        let name: String = row.try_get("poke_name")?;
-        println!("{}", name);
+// Every file is checked as pokemon data before being stored, unreadable or
+// invalid ones are skipped
+async fn import_dex(path: &Path, db_co: &Pool<Postgres>) -> Result<usize, PokeError> {
+    let files = import::pokemon_files(path)?;
+    // A name stored under another id (renamed upstream) goes to the new id
+    let db_delete = "DELETE FROM dex_entry WHERE dex_name=$1 AND dex_id<>$2";
+    let db_insert = "INSERT INTO dex_entry (dex_id, dex_name, dex_data) VALUES ($1, $2, $3::json)
+        ON CONFLICT (dex_id) DO UPDATE SET dex_name=EXCLUDED.dex_name, dex_data=EXCLUDED.dex_data, dex_imported_at=now()";
+
+    let mut imported = 0;
+    let mut db_tx = db_co.begin().await?;
+    for file in &files {
+        let poke = tokio::fs::read_to_string(file)
+            .await
+            .map_err(PokeError::from)
+            .and_then(|body| Ok((serde_json::from_str::<Pokemon>(&body)?, body)));
+        let (poke, body): (DbPoke, String) = match poke {
+            Ok((poke, body)) => (poke.into(), body),
+            Err(e) => {
+                eprintln!("Skipping {}: {}", file.display(), e);
+                continue;
+            }
+        };
+        sqlx::query(db_delete)
+            .bind(&poke.name)
+            .bind(poke.id)
+            .execute(&mut *db_tx)
+            .await?;
+        sqlx::query(db_insert)
+            .bind(poke.id)
+            .bind(&poke.name)
+            .bind(&body)
+            .execute(&mut *db_tx)
+            .await?;
+        imported += 1;
     }
+    db_tx.commit().await?;
 
-    Ok(())
+    eprintln!(
+        "Imported {} pokemon from {} ({} files skipped)",
+        imported,
+        path.display(),
+        files.len() - imported
+    );
+    Ok(imported)
 }
 
-async fn multi_catch_pokemon(
-    client: Client,
-    names: Vec<String>,
+// No target resumes the saved hunt of the pokemon
+async fn shiny_pokemon(
+    name: &str,
+    instance: Option<i64>,
+    target: Option<Target>,
+    options: &MineOptions,
+    trainer_id: i64,
     db_co: &Pool<Postgres>,
-) -> Result<(), task::JoinError> {
-    let poke = names.into_iter().map(|name| {
-        let client = client.clone();
-        let db_co = db_co.clone();
+) -> Result<ShinyProof, PokeError> {
+    let (row, resume) = match &target {
+        Some(_) => {
+            // Oldest non shiny instance unless one is given, a shiny one is only
+            // hunted again on request as its proof is replaced
+            let db_select = "SELECT poke_id, instance_id, instance_is_shiny, hunt_attempts FROM poke JOIN poke_instance USING (poke_id) LEFT JOIN shiny_hunt USING (instance_id) WHERE poke_name=$1 AND trainer_id=$2 AND ($3::bigint IS NULL OR instance_id=$3) ORDER BY instance_is_shiny, instance_id LIMIT 1";
+            let row = sqlx::query(db_select)
+                .bind(name)
+                .bind(trainer_id)
+                .bind(instance)
+                .fetch_optional(db_co)
+                .await?
+                .ok_or_else(|| PokeError::NotInCollection(name.to_string()))?;
+            if instance.is_none() && row.try_get::<bool, _>("instance_is_shiny")? {
+                return Err(PokeError::ShinyHunt(format!(
+                    "every {} is already shiny, use --instance to hunt one again",
+                    name
+                )));
+            }
+            if row.try_get::<Option<i64>, _>("hunt_attempts")?.is_some() {
+                eprintln!("Starting over, the saved hunt of this pokemon will be replaced (use --resume to continue it)");
+            }
+            (row, None)
+        }
+        None => {
+            // Most recently saved hunt unless an instance is given
+            let db_select = "SELECT * FROM shiny_hunt JOIN poke_instance USING (instance_id) JOIN poke USING (poke_id) WHERE poke_name=$1 AND trainer_id=$2 AND ($3::bigint IS NULL OR instance_id=$3) ORDER BY hunt_updated_at DESC LIMIT 1";
+            let row = sqlx::query(db_select)
+                .bind(name)
+                .bind(trainer_id)
+                .bind(instance)
+                .fetch_optional(db_co)
+                .await?
+                .ok_or_else(|| PokeError::NoSavedHunt(name.to_string()))?;
+            let frontier: Vec<i64> = row.try_get("hunt_frontier")?;
+            let attempts: i64 = row.try_get("hunt_attempts")?;
+            let state = HuntState {
+                frontier: frontier.into_iter().map(|nonce| nonce as u64).collect(),
+                attempts: attempts as u64,
+            };
+            (row, Some(state))
+        }
+    };
+    let identity = HuntIdentity {
+        trainer_id,
+        poke_id: row.try_get("poke_id")?,
+        instance_id: row.try_get("instance_id")?,
+    };
+    let target = match target {
+        Some(target) => target,
+        None => row
+            .try_get::<String, _>("hunt_target")?
+            .parse()
+            .map_err(PokeError::ShinyHunt)?,
+    };
+
+    // Ctrl-C stops the workers, the hunt is saved and can be resumed
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let ctrl_c = {
+        let interrupt = interrupt.clone();
         task::spawn(async move {
-            let res = catch_pokemon(client, &name, &db_co).await;
-            match res {
-                Ok(_) => (),
-                Err(e) => eprintln!("Error during multi catch on {}", name),
+            if tokio::signal::ctrl_c().await.is_ok() {
+                interrupt.store(true, Ordering::SeqCst);
             }
         })
+    };
+
+    let options = MineOptions {
+        interrupt: Some(interrupt),
+        ..options.clone()
+    };
+    let (tx_checkpoint, mut rx_checkpoint) = tokio::sync::mpsc::unbounded_channel();
+    let hunt_target = target.clone();
+    let hunt = task::spawn_blocking(move || {
+        shiny::mine(identity, hunt_target, resume, &options, |state| {
+            let _ = tx_checkpoint.send(state.clone());
+        })
     });
 
-    for poke in poke {
-        poke.await?;
+    // Checkpoints are written while mining, the channel closes when the hunt ends
+    while let Some(state) = rx_checkpoint.recv().await {
+        if let Err(e) = save_hunt(identity.instance_id, &target, &state, db_co).await {
+            eprintln!("Could not save shiny hunt: {}", e);
+        }
     }
+    ctrl_c.abort();
 
-    Ok(())
-}
+    // Nothing is written on the pokemon if the hunt fails or is stopped
+    let proof = hunt.await??;
+    eprintln!("Shiny found with : {}", proof.nonce);
+
+    let mut db_tx = db_co.begin().await?;
+    let db_update = "UPDATE poke_instance SET instance_is_shiny=true, instance_shiny_source='hunt', instance_shiny_nonce=$2, instance_shiny_target=$3 WHERE instance_id=$1";
+    sqlx::query(db_update)
+        .bind(identity.instance_id)
+        .bind(proof.nonce as i64)
+        .bind(proof.target.to_string())
+        .execute(&mut *db_tx)
+        .await?;
+    sqlx::query("DELETE FROM shiny_hunt WHERE instance_id=$1")
+        .bind(identity.instance_id)
+        .execute(&mut *db_tx)
+        .await?;
+    db_tx.commit().await?;
 
-// Shiny imitate "blockchain mining" => Leading 0 (or other number) to find in a hash
-fn generate_hash(input: u64) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(input.to_string());
-    let result = hasher.finalize();
-    format!("{:x}", result)
+    eprintln!("{} #{} is now shiny", name, identity.instance_id);
+    Ok(proof)
 }
 
-fn is_shiny(hash: &str, difficulty: &usize, number: usize) -> bool {
-    let number = format!("{}", number);
-    hash.starts_with(&number.repeat(*difficulty))
+async fn save_hunt(
+    instance_id: i64,
+    target: &Target,
+    state: &HuntState,
+    db_co: &Pool<Postgres>,
+) -> Result<(), PokeError> {
+    let frontier: Vec<i64> = state.frontier.iter().map(|nonce| *nonce as i64).collect();
+    let db_upsert = "INSERT INTO shiny_hunt (instance_id, hunt_target, hunt_frontier, hunt_attempts) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (instance_id) DO UPDATE SET hunt_target=EXCLUDED.hunt_target, hunt_frontier=EXCLUDED.hunt_frontier, hunt_attempts=EXCLUDED.hunt_attempts, hunt_updated_at=now()";
+    sqlx::query(db_upsert)
+        .bind(instance_id)
+        .bind(target.to_string())
+        .bind(frontier)
+        .bind(state.attempts as i64)
+        .execute(db_co)
+        .await?;
+    Ok(())
 }
 
-// CLI parsers
+// Recompute the hash of every hunted shiny instance (or the given one) from its stored proof
+// Encounter shinies have nothing to prove
+async fn verify_shiny(
+    name: &str,
+    instance: Option<i64>,
+    trainer_id: i64,
+    db_co: &Pool<Postgres>,
+) -> Result<Vec<ShinyCheck>, PokeError> {
+    let db_select = "SELECT * FROM poke JOIN poke_instance USING (poke_id) WHERE poke_name=$1 AND trainer_id=$2 AND instance_is_shiny AND instance_shiny_source IS DISTINCT FROM 'encounter' AND ($3::bigint IS NULL OR instance_id=$3) ORDER BY instance_id";
+    let rows = sqlx::query(db_select)
+        .bind(name)
+        .bind(trainer_id)
+        .bind(instance)
+        .fetch_all(db_co)
+        .await?;
+    if rows.is_empty() {
+        return Err(PokeError::InvalidShinyProof(format!(
+            "no hunted shiny {} in the collection",
+            name
+        )));
+    }
 
-fn parse_difficulty_and_number(input: &str) -> Result<usize, String> {
-    let num: usize = input.parse().unwrap();
-    if num > 0 && num < 10 {
-        Ok(num)
-    } else {
-        Err("Difficulty and number must be between 1 and 9".to_string())
+    let mut checks = Vec::new();
+    for row in rows.iter() {
+        let identity = HuntIdentity {
+            trainer_id,
+            poke_id: row.try_get("poke_id")?,
+            instance_id: row.try_get("instance_id")?,
+        };
+        checks.push(ShinyCheck::new(
+            name.to_string(),
+            &identity,
+            proof_from_row(row)?,
+        ));
     }
+    Ok(checks)
 }
 
-// Different because generation can update
-fn parse_generation(input: &str) -> Result<usize, String> {
-    let num: usize = input.parse().unwrap();
-    if num > 0 && num <= 9 {
-        Ok(num)
+async fn collection_pokemon(
+    gen: Option<&Generation>,
+    trainer_id: i64,
+    db_co: &Pool<Postgres>,
+) -> Result<Vec<DbPoke>, PokeError> {
+    let db_select: &str;
+    let rows: Vec<PgRow>;
+
+    if let Some(gen) = gen {
+        db_select = "SELECT * FROM poke JOIN poke_instance USING (poke_id) WHERE trainer_id=$1 AND poke_id BETWEEN $2 AND $3 ORDER BY poke_id, instance_id";
+        rows = sqlx::query(db_select)
+            .bind(trainer_id)
+            .bind(gen.start())
+            .bind(gen.end())
+            .fetch_all(db_co)
+            .await?;
     } else {
-        Err("Generation must be between 1 and 9".to_string())
+        db_select = "SELECT * FROM poke JOIN poke_instance USING (poke_id) WHERE trainer_id=$1 ORDER BY poke_id, instance_id";
+        rows = sqlx::query(db_select)
+            .bind(trainer_id)
+            .fetch_all(db_co)
+            .await?;
     }
+
+    pokes_from_rows(&rows)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+// Conditional requests make unchanged species cheap, only rows whose stored
+// fields differ from the API are written
+async fn refresh_collection(
+    api: &PokeApi,
+    trainer_id: i64,
+    db_co: &Pool<Postgres>,
+) -> Result<Vec<RefreshReport>, PokeError> {
+    let db_select = "SELECT * FROM poke WHERE poke_id IN (SELECT poke_id FROM poke_instance WHERE trainer_id=$1) ORDER BY poke_id";
+    let rows = sqlx::query(db_select)
+        .bind(trainer_id)
+        .fetch_all(db_co)
+        .await?;
+
+    let mut reports = Vec::new();
+    for row in rows.iter() {
+        let stored = poke_from_row(row)?;
+        let res = refresh_pokemon(api, &stored, db_co).await;
+        reports.push(RefreshReport::new(&stored, res));
+    }
+    Ok(reports)
+}
+
+async fn refresh_pokemon(
+    api: &PokeApi,
+    stored: &DbPoke,
+    db_co: &Pool<Postgres>,
+) -> Result<Vec<&'static str>, PokeError> {
+    // By id, a species renamed upstream is still found
+    // An unchanged resource is answered with a 304 and read from the cache, it is
+    // still compared as the stored row can be older than the cached response
+    let body = api.revalidate_pokemon(&stored.id.to_string()).await?;
+    let upstream: DbPoke = serde_json::from_slice::<Pokemon>(&body)?.into();
+
+    let changes = refresh::changes(stored, &upstream)?;
+    if changes.is_empty() {
+        return Ok(changes);
+    }
+
+    let db_update = "UPDATE poke SET poke_name=$2, poke_type=$3::json, poke_base_experience=$4, poke_stats=$5::json, poke_sprites=$6::json WHERE poke_id=$1";
+    sqlx::query(db_update)
+        .bind(stored.id)
+        .bind(&upstream.name)
+        .bind(serde_json::to_string(&upstream.types)?)
+        .bind(upstream.base_experience)
+        .bind(serde_json::to_string(&upstream.stats)?)
+        .bind(serde_json::to_string(&upstream.sprites)?)
+        .execute(db_co)
+        .await?;
+    Ok(changes)
+}
+
+// Images come from the database or the API cache, a missing one only leaves its card without picture
+async fn export_gallery(
+    api: &PokeApi,
+    dir: &Path,
+    trainer_id: i64,
+    db_co: &Pool<Postgres>,
+) -> Result<(), PokeError> {
+    let pokes = collection_pokemon(None, trainer_id, db_co).await?;
+    tokio::fs::create_dir_all(dir.join("sprites")).await?;
+
+    let mut images = HashMap::new();
+    for poke in &pokes {
+        let shiny = poke.shiny_count() > 0;
+        let url = match poke.sprites.front(shiny) {
+            Some(url) => url.to_string(),
+            None => {
+                match find_sprite(api, &poke.name, None, SpriteQuery::front(shiny), db_co).await {
+                    Ok(sprite) => sprite.url,
+                    Err(e) => {
+                        eprintln!("No sprite for {}: {}", poke.name, e);
+                        continue;
+                    }
+                }
+            }
+        };
+        let bytes = match load_sprite(api, &url, db_co).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                eprintln!("Could not load the sprite of {}: {}", poke.name, e);
+                continue;
+            }
+        };
+
+        let file = format!(
+            "sprites/{}{}.{}",
+            poke.id,
+            if shiny { "-shiny" } else { "" },
+            gallery::image_extension(&url)
+        );
+        if let Err(e) = tokio::fs::write(dir.join(&file), bytes).await {
+            eprintln!("Could not write the sprite of {}: {}", poke.name, e);
+            continue;
+        }
+        images.insert(poke.id, file);
+    }
+
+    let pages = gallery::pages(&pokes, &images);
+    for page in &pages {
+        tokio::fs::write(dir.join(&page.file), &page.html).await?;
+    }
+    tokio::fs::write(dir.join(gallery::STYLE_FILE), gallery::STYLE).await?;
+
+    eprintln!(
+        "Gallery of {} pokemon ({} pages, {} images) written to {}",
+        pokes.len(),
+        pages.len(),
+        images.len(),
+        dir.display()
+    );
+    Ok(())
+}
+
+// At most concurrency catches run at the same time, reports are in the order of names
+async fn multi_catch_pokemon(
+    api: &PokeApi,
+    mut names: Vec<String>,
+    options: &CatchOptions,
+    concurrency: usize,
+    trainer_id: i64,
+    db_co: &Pool<Postgres>,
+) -> Result<Vec<CatchReport>, PokeError> {
+    // Concurrent catches of the same species would both pass the already caught
+    // check, names and dex numbers (pikachu, 25) are compared by dex id
+    if !options.again {
+        let mut ids = species_ids(api, &names, concurrency, db_co)
+            .await?
+            .into_iter();
+        let mut seen = HashSet::new();
+        names.retain(|name| {
+            let key = match ids.next().flatten() {
+                Some(id) => id.to_string(),
+                None => catch::normalize_name(name),
+            };
+            seen.insert(key)
+        });
+    }
+
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let tasks: Vec<_> = names
+        .into_iter()
+        .map(|name| {
+            let api = api.clone();
+            let permits = permits.clone();
+            let options = CatchOptions {
+                encounter: options.encounter.as_ref().map(|encounter| encounter.fork()),
+                ..options.clone()
+            };
+            let db_co = db_co.clone();
+            task::spawn(async move {
+                // The semaphore is never closed
+                let _permit = permits.acquire_owned().await;
+                let res = catch_pokemon(&api, &name, &options, trainer_id, &db_co).await;
+                CatchReport::new(&name, res)
+            })
+        })
+        .collect();
+
+    let mut reports = Vec::new();
+    for task in tasks {
+        reports.push(task.await?);
+    }
+
+    Ok(reports)
+}
+
+// Dex id of each name, None when it cannot be found (its catch reports why)
+async fn species_ids(
+    api: &PokeApi,
+    names: &[String],
+    concurrency: usize,
+    db_co: &Pool<Postgres>,
+) -> Result<Vec<Option<i64>>, PokeError> {
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let tasks: Vec<_> = names
+        .iter()
+        .map(|name| {
+            let api = api.clone();
+            let permits = permits.clone();
+            let name = catch::normalize_name(name);
+            let db_co = db_co.clone();
+            task::spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                species_id(&api, &name, &db_co).await.ok()
+            })
+        })
+        .collect();
+
+    let mut ids = Vec::new();
+    for task in tasks {
+        ids.push(task.await?);
+    }
+    Ok(ids)
+}
+
+// Stored species first, others from their pokemon data (then served by the cache
+// to the catch)
+async fn species_id(api: &PokeApi, name: &str, db_co: &Pool<Postgres>) -> Result<i64, PokeError> {
+    if let Ok(id) = name.parse::<i64>() {
+        return Ok(id);
+    }
+    let db_select = "SELECT poke_id FROM poke WHERE poke_name=$1";
+    if let Some(row) = sqlx::query(db_select)
+        .bind(name)
+        .fetch_optional(db_co)
+        .await?
+    {
+        return Ok(row.try_get("poke_id")?);
+    }
+
+    let body = pokemon_data(api, name, db_co).await?;
+    let poke: DbPoke = serde_json::from_slice::<Pokemon>(&body)?.into();
+    Ok(poke.id)
+}
+
+// Caught and shiny counts for each generation
+async fn dex_pokemon(
+    api: &PokeApi,
+    gen: Option<&Generation>,
+    missing: bool,
+    trainer_id: i64,
+    db_co: &Pool<Postgres>,
+) -> Result<Vec<DexGeneration>, PokeError> {
+    let db_select = "SELECT poke_id, bool_or(instance_is_shiny) AS shiny FROM poke_instance WHERE trainer_id=$1 GROUP BY poke_id";
+    let rows = sqlx::query(db_select)
+        .bind(trainer_id)
+        .fetch_all(db_co)
+        .await?;
+
+    // Species id => has a shiny
+    let mut caught: HashMap<i64, bool> = HashMap::new();
+    for row in rows.iter() {
+        caught.insert(row.try_get("poke_id")?, row.try_get("shiny")?);
+    }
+
+    let gens: Vec<Generation> = match gen {
+        Some(gen) => vec![*gen],
+        None => Generation::ALL.to_vec(),
+    };
+
+    let mut dex = Vec::new();
+    for gen in gens {
+        let in_gen: Vec<bool> = gen
+            .ids()
+            .filter_map(|id| caught.get(&id).copied())
+            .collect();
+
+        let mut dex_gen = DexGeneration {
+            gen,
+            total: gen.total(),
+            caught: in_gen.len() as i64,
+            shiny: in_gen.iter().filter(|shiny| **shiny).count() as i64,
+            missing: Vec::new(),
+        };
+
+        if missing {
+            // Names come from the imported dex, then the API, only numbers are
+            // shown if neither has them
+            let db_select = "SELECT dex_id, dex_name FROM dex_entry WHERE dex_id BETWEEN $1 AND $2";
+            let mut names: HashMap<i64, String> = HashMap::new();
+            for row in sqlx::query(db_select)
+                .bind(gen.start())
+                .bind(gen.end())
+                .fetch_all(db_co)
+                .await?
+            {
+                names.insert(row.try_get("dex_id")?, row.try_get("dex_name")?);
+            }
+            if (names.len() as i64) < gen.total() {
+                match api.fetch_species_list(gen.start(), gen.end()).await {
+                    Ok(list) => {
+                        for (id, name) in list.entries() {
+                            names.entry(id).or_insert(name);
+                        }
+                    }
+                    Err(e) => eprintln!("Could not get pokemon names: {}", e),
+                }
+            }
+            dex_gen.missing = gen
+                .ids()
+                .filter(|id| !caught.contains_key(id))
+                .map(|id| (id, names.get(&id).cloned()))
+                .collect();
+        }
+
+        dex.push(dex_gen);
+    }
+
+    Ok(dex)
+}
+
+async fn trainer_id(name: &str, db_co: &Pool<Postgres>) -> Result<i64, PokeError> {
+    let db_select = "SELECT trainer_id FROM trainer WHERE trainer_name=$1";
+    let row = sqlx::query(db_select)
+        .bind(name)
+        .fetch_optional(db_co)
+        .await?
+        .ok_or_else(|| PokeError::UnknownTrainer(name.to_string()))?;
+    Ok(row.try_get("trainer_id")?)
+}
+
+async fn create_trainer(name: &str, db_co: &Pool<Postgres>) -> Result<i64, PokeError> {
+    let db_insert = "INSERT INTO trainer (trainer_name) VALUES ($1) RETURNING trainer_id";
+    let row = sqlx::query(db_insert)
+        .bind(name)
+        .fetch_one(db_co)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db_e) if db_e.is_unique_violation() => {
+                PokeError::TrainerExists(name.to_string())
+            }
+            e => e.into(),
+        })?;
+
+    eprintln!("Trainer {} created", name);
+    Ok(row.try_get("trainer_id")?)
+}
+
+async fn list_trainers(db_co: &Pool<Postgres>) -> Result<Vec<Trainer>, PokeError> {
+    let db_select = "SELECT trainer_id, trainer_name, trainer_created_at, COUNT(instance_id) AS caught FROM trainer LEFT JOIN poke_instance USING (trainer_id) GROUP BY trainer_id ORDER BY trainer_name";
+    let rows = sqlx::query(db_select).fetch_all(db_co).await?;
+
+    let mut trainers = Vec::new();
+    for row in rows.iter() {
+        trainers.push(Trainer {
+            id: row.try_get("trainer_id")?,
+            name: row.try_get("trainer_name")?,
+            created_at: row.try_get("trainer_created_at")?,
+            caught: row.try_get("caught")?,
+        });
+    }
+
+    Ok(trainers)
+}
+
+// Caught instances are deleted with the trainer, species data is kept
+// The default trainer stays, every command without --trainer relies on it
+async fn delete_trainer(name: &str, db_co: &Pool<Postgres>) -> Result<(), PokeError> {
+    if name == DEFAULT_TRAINER {
+        return Err(PokeError::Config(format!(
+            "The {} trainer cannot be deleted",
+            DEFAULT_TRAINER
+        )));
+    }
+    let db_delete = "DELETE FROM trainer WHERE trainer_name=$1";
+    let res = sqlx::query(db_delete).bind(name).execute(db_co).await?;
+    if res.rows_affected() == 0 {
+        return Err(PokeError::UnknownTrainer(name.to_string()));
+    }
+
+    eprintln!("Trainer {} deleted", name);
+    Ok(())
+}
+
+// CLI parsers
+
+fn parse_hex_digit(input: &str) -> Result<u8, String> {
+    match u8::from_str_radix(input, 16) {
+        Ok(digit) if input.len() == 1 => Ok(digit),
+        _ => Err("Digit must be a single hex digit (0 to f)".to_string()),
+    }
+}
+
+// A target spec, or the count of the old "<count> <digit>" form which needs the
+// digit and is checked by parse_target
+fn parse_target_arg(input: &str) -> Result<String, String> {
+    if input.parse::<usize>().is_err() {
+        input.parse::<Target>()?;
+    }
+    Ok(input.to_string())
+}
+
+// A target spec, or the old "<count> <digit>" form
+fn parse_target(target: &str, digit: Option<u8>) -> Result<Target, String> {
+    match digit {
+        Some(digit) => match target.parse::<usize>() {
+            Ok(count) if (1..=64).contains(&count) => Ok(Target::repeat(digit, count)),
+            _ => Err(format!(
+                "Number of leading digits must be between 1 and 64, got {}",
+                target
+            )),
+        },
+        None if target.parse::<usize>().is_ok() => Err(format!(
+            "Missing <DIGIT> after the number of leading digits {}",
+            target
+        )),
+        None => target.parse(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use catch::CatchStatus;
     use dotenv::dotenv;
+    use mock_api::MockPokeApi;
+    use reqwest::Client;
     use sqlx::{migrate, PgPool};
     use std::env;
+    use tokio::sync::{Mutex, MutexGuard};
+
+    // Tests share the same DB, the guard makes them run one at a time
+    static DB_LOCK: Mutex<()> = Mutex::const_new(());
+
+    // Every test starts with an empty DB and a single trainer
+    async fn setup_test_db() -> (PgPool, i64, MutexGuard<'static, ()>) {
+        let guard = DB_LOCK.lock().await;
 
-    async fn setup_test_db() -> PgPool {
         // Init dotenv for DB
         dotenv().ok();
 
@@ -391,27 +1510,39 @@ mod tests {
         migrate!("./migrations").run(&pool).await.unwrap();
 
         // Empty DB
-        sqlx::query("TRUNCATE TABLE poke")
+        sqlx::query("TRUNCATE TABLE poke, trainer, sprite_image, dex_entry CASCADE")
             .execute(&pool)
             .await
             .expect("Failed to truncate table");
+        let trainer_id = create_trainer("ash", &pool)
+            .await
+            .expect("Failed to create trainer");
 
-        pool
+        (pool, trainer_id, guard)
     }
 
-    fn setup_test_reqwest() -> Client {
-        ClientBuilder::new()
-            .connect_timeout(std::time::Duration::from_secs(10))
-            .build()
-            .unwrap_or_default()
+    async fn count_poke(pool: &PgPool) -> i64 {
+        sqlx::query("SELECT COUNT(*) FROM poke")
+            .fetch_one(pool)
+            .await
+            .expect("Could not count rows")
+            .get(0)
     }
 
     #[tokio::test]
     async fn test_catch_pokemon() {
-        let pool = setup_test_db().await;
-        let client = setup_test_reqwest();
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+        let api = mock.api();
         let poke_name = String::from("pikachu");
-        let res = catch_pokemon(client, &poke_name, &pool).await;
+        let res = catch_pokemon(
+            &api,
+            &poke_name,
+            &CatchOptions::default(),
+            trainer_id,
+            &pool,
+        )
+        .await;
         assert!(res.is_ok());
 
         // Verify result in DB
@@ -429,4 +1560,827 @@ mod tests {
 
         assert_eq!(bdd_poke_name, poke_name);
     }
+
+    #[tokio::test]
+    async fn test_catch_pokemon_by_id() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+
+        catch_pokemon(
+            &mock.api(),
+            "151",
+            &CatchOptions::default(),
+            trainer_id,
+            &pool,
+        )
+        .await
+        .expect("Could not catch by id");
+
+        let name: String = sqlx::query("SELECT poke_name FROM poke WHERE poke_id=151")
+            .fetch_one(&pool)
+            .await
+            .expect("Could not get the row")
+            .get("poke_name");
+        assert_eq!(name, "mew");
+    }
+
+    #[tokio::test]
+    async fn test_catch_unknown_pokemon() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+
+        let res = catch_pokemon(
+            &mock.api(),
+            "missingno",
+            &CatchOptions::default(),
+            trainer_id,
+            &pool,
+        )
+        .await;
+        assert!(matches!(res, Err(PokeError::UnknownPokemon(_))));
+        assert_eq!(count_poke(&pool).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_catch_rate_limited() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+        mock.rate_limited("pokemon", "pikachu", Some(1)).await;
+
+        let res = catch_pokemon(
+            &mock.api(),
+            "pikachu",
+            &CatchOptions::default(),
+            trainer_id,
+            &pool,
+        )
+        .await;
+        assert!(matches!(
+            res,
+            Err(PokeError::RateLimited {
+                retry_after: Some(1)
+            })
+        ));
+        assert_eq!(count_poke(&pool).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_multi_catch_pokemon() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+
+        let names = vec![
+            String::from("bulbasaur"),
+            String::from("charmander"),
+            String::from("missingno"),
+        ];
+        let reports = multi_catch_pokemon(
+            &mock.api(),
+            names,
+            &CatchOptions::default(),
+            2,
+            trainer_id,
+            &pool,
+        )
+        .await
+        .expect("Multi catch tasks failed");
+
+        // Unknown pokemon is skipped, the others are caught
+        assert_eq!(count_poke(&pool).await, 2);
+        let statuses: Vec<CatchStatus> = reports.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                CatchStatus::Caught,
+                CatchStatus::Caught,
+                CatchStatus::NotFound
+            ]
+        );
+
+        // Duplicates are caught once, owned ones are reported
+        let names = vec![
+            String::from("bulbasaur"),
+            String::from("eevee"),
+            String::from("Eevee"),
+        ];
+        let reports = multi_catch_pokemon(
+            &mock.api(),
+            names,
+            &CatchOptions::default(),
+            2,
+            trainer_id,
+            &pool,
+        )
+        .await
+        .expect("Multi catch tasks failed");
+        let statuses: Vec<CatchStatus> = reports.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, [CatchStatus::AlreadyOwned, CatchStatus::Caught]);
+        assert!(reports[1].instance.is_some());
+
+        // Same species by name and by dex number, caught once
+        let names = vec![String::from("pikachu"), String::from("25")];
+        let reports = multi_catch_pokemon(
+            &mock.api(),
+            names,
+            &CatchOptions::default(),
+            2,
+            trainer_id,
+            &pool,
+        )
+        .await
+        .expect("Multi catch tasks failed");
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].status, CatchStatus::Caught);
+        let pikachu = info_pokemon("pikachu", trainer_id, &pool).await.unwrap();
+        assert_eq!(pikachu.count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_catch_by_dex_number() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+
+        let names = catch::expand_names(&[String::from("025"), String::from("1-1")]).unwrap();
+        let reports = multi_catch_pokemon(
+            &mock.api(),
+            names,
+            &CatchOptions::default(),
+            2,
+            trainer_id,
+            &pool,
+        )
+        .await
+        .expect("Multi catch tasks failed");
+        let names: Vec<&str> = reports.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["pikachu", "bulbasaur"]);
+
+        // Same species by name or by number
+        for name in ["pikachu", "Pikachu", "25", "0001"] {
+            let res = catch_pokemon(
+                &mock.api(),
+                name,
+                &CatchOptions::default(),
+                trainer_id,
+                &pool,
+            )
+            .await;
+            assert!(matches!(res, Err(PokeError::AlreadyCaught(_))), "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_collection() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+        let dir = env::temp_dir().join(format!("poke-collect-refresh-test-{}", std::process::id()));
+        let api = mock
+            .api()
+            .with_cache(ResponseCache::new(dir.clone(), Duration::from_secs(60)));
+        for name in ["pikachu", "eevee"] {
+            catch_pokemon(&api, name, &CatchOptions::default(), trainer_id, &pool)
+                .await
+                .expect("Could not catch");
+        }
+        assert_eq!(mock.request_count().await, 2);
+
+        // Species are revalidated by id, the first refresh fills the cache
+        let reports = refresh_collection(&api, trainer_id, &pool).await.unwrap();
+        assert!(reports.iter().all(|r| r.status == RefreshStatus::Unchanged));
+        assert_eq!(mock.request_count().await, 4);
+
+        // Then both cached responses are revalidated and answered with a 304
+        let reports = refresh_collection(&api, trainer_id, &pool).await.unwrap();
+        assert!(reports.iter().all(|r| r.status == RefreshStatus::Unchanged));
+        assert_eq!(mock.request_count().await, 6);
+        assert_eq!(mock.conditional_count().await, 2);
+
+        let body = api.fetch_pokemon("25").await.unwrap();
+        let mut json: Value = serde_json::from_slice(&body).unwrap();
+        json["base_experience"] = 120.into();
+        mock.update("pokemon", "25", &json).await;
+
+        let reports = refresh_collection(&api, trainer_id, &pool).await.unwrap();
+        assert_eq!(reports[0].name, "pikachu");
+        assert_eq!(reports[0].status, RefreshStatus::Updated);
+        assert_eq!(reports[0].changes, ["base_experience"]);
+        assert_eq!(reports[1].status, RefreshStatus::Unchanged);
+        assert_eq!(mock.conditional_count().await, 4);
+        let pikachu = info_pokemon("pikachu", trainer_id, &pool).await.unwrap();
+        assert_eq!(pikachu.base_experience, 120);
+
+        // Renamed upstream, only the id still answers
+        json["name"] = "pikachu-partner".into();
+        mock.update("pokemon", "25", &json).await;
+        let reports = refresh_collection(&api, trainer_id, &pool).await.unwrap();
+        assert_eq!(reports[0].changes, ["name"]);
+        assert!(info_pokemon("pikachu-partner", trainer_id, &pool)
+            .await
+            .is_ok());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_import_dex() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures");
+        assert_eq!(import_dex(&fixtures, &pool).await.unwrap(), 5);
+        // Imported again without duplicates
+        assert_eq!(import_dex(&fixtures, &pool).await.unwrap(), 5);
+
+        // Unroutable and offline, the API is never used
+        let api = PokeApi::new(Client::new(), "http://127.0.0.1:9/api/v2/").offline(true);
+        let pikachu = catch_pokemon(&api, "25", &CatchOptions::default(), trainer_id, &pool)
+            .await
+            .expect("Could not catch pikachu offline");
+        assert_eq!(pikachu.name, "pikachu");
+        // Caught species by dex number, not the imported entry
+        let pikachu = info_pokemon("25", trainer_id, &pool).await.unwrap();
+        assert_eq!((pikachu.name.as_str(), pikachu.count()), ("pikachu", 1));
+        // Refresh goes to the API, not to the imported dex
+        let options = CatchOptions {
+            refresh: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            catch_pokemon(&api, "pikachu", &options, trainer_id, &pool).await,
+            Err(PokeError::OfflineMiss(_))
+        ));
+
+        let eevee = info_pokemon("eevee", trainer_id, &pool).await.unwrap();
+        assert_eq!((eevee.id, eevee.count()), (133, 0));
+        assert!(matches!(
+            info_pokemon("missingno", trainer_id, &pool).await,
+            Err(PokeError::NotInCollection(_))
+        ));
+
+        let dex = dex_pokemon(&api, Some(&Generation::Kanto), true, trainer_id, &pool)
+            .await
+            .unwrap();
+        assert_eq!(dex[0].caught, 1);
+        assert!(dex[0].missing.contains(&(151, Some(String::from("mew")))));
+
+        // Name already stored under another id, and a file that is not UTF-8
+        sqlx::query("UPDATE dex_entry SET dex_id=9999 WHERE dex_name='eevee'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let dir = env::temp_dir().join(format!(
+            "poke-collect-import-dex-test-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy(fixtures.join("pokemon/eevee.json"), dir.join("eevee.json")).unwrap();
+        std::fs::write(dir.join("broken.json"), [0xff, 0xfe, 0x00]).unwrap();
+        assert_eq!(import_dex(&dir, &pool).await.unwrap(), 1);
+        let ids: Vec<i64> = sqlx::query("SELECT dex_id FROM dex_entry WHERE dex_name='eevee'")
+            .fetch_all(&pool)
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.get("dex_id"))
+            .collect();
+        assert_eq!(ids, [133]);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_catch_twice() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+
+        catch_pokemon(
+            &mock.api(),
+            "eevee",
+            &CatchOptions::default(),
+            trainer_id,
+            &pool,
+        )
+        .await
+        .expect("Could not catch eevee");
+        let res = catch_pokemon(
+            &mock.api(),
+            "eevee",
+            &CatchOptions::default(),
+            trainer_id,
+            &pool,
+        )
+        .await;
+        assert!(matches!(res, Err(PokeError::AlreadyCaught(_))));
+    }
+
+    #[tokio::test]
+    async fn test_info_not_in_collection() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+
+        let res = info_pokemon("pikachu", trainer_id, &pool).await;
+        assert!(matches!(res, Err(PokeError::NotInCollection(_))));
+    }
+
+    #[tokio::test]
+    async fn test_catch_refresh_keeps_shiny() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+
+        catch_pokemon(
+            &mock.api(),
+            "pikachu",
+            &CatchOptions::default(),
+            trainer_id,
+            &pool,
+        )
+        .await
+        .expect("Could not catch pikachu");
+        sqlx::query("UPDATE poke SET poke_base_experience=0 WHERE poke_id=25")
+            .execute(&pool)
+            .await
+            .expect("Could not update pikachu");
+        sqlx::query("UPDATE poke_instance SET instance_is_shiny=true WHERE poke_id=25")
+            .execute(&pool)
+            .await
+            .expect("Could not update pikachu");
+
+        // By id, already caught under its name
+        let res = catch_pokemon(
+            &mock.api(),
+            "25",
+            &CatchOptions::default(),
+            trainer_id,
+            &pool,
+        )
+        .await;
+        assert!(matches!(res, Err(PokeError::AlreadyCaught(name)) if name == "pikachu"));
+
+        let options = CatchOptions {
+            refresh: true,
+            ..Default::default()
+        };
+        catch_pokemon(&mock.api(), "pikachu", &options, trainer_id, &pool)
+            .await
+            .expect("Could not refresh pikachu");
+        let pikachu = info_pokemon("pikachu", trainer_id, &pool)
+            .await
+            .expect("Could not get pikachu");
+        assert_eq!(pikachu.base_experience, 112);
+        assert_eq!(pikachu.count(), 1);
+        assert_eq!(pikachu.shiny_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_catch_again() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+
+        catch_pokemon(
+            &mock.api(),
+            "pikachu",
+            &CatchOptions::default(),
+            trainer_id,
+            &pool,
+        )
+        .await
+        .expect("Could not catch pikachu");
+        let options = CatchOptions {
+            again: true,
+            nickname: Some(String::from("Sparky")),
+            ..Default::default()
+        };
+        catch_pokemon(&mock.api(), "pikachu", &options, trainer_id, &pool)
+            .await
+            .expect("Could not catch a second pikachu");
+
+        let pikachu = info_pokemon("pikachu", trainer_id, &pool)
+            .await
+            .expect("Could not get pikachu");
+        assert_eq!(pikachu.count(), 2);
+        assert_eq!(pikachu.instances[0].nickname, None);
+        assert_eq!(pikachu.instances[1].nickname.as_deref(), Some("Sparky"));
+    }
+
+    #[tokio::test]
+    async fn test_trainer_collections() {
+        let (pool, ash_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+        let misty_id = create_trainer("misty", &pool)
+            .await
+            .expect("Could not create misty");
+        assert!(matches!(
+            create_trainer("misty", &pool).await,
+            Err(PokeError::TrainerExists(_))
+        ));
+
+        // Same species in two boxes
+        for trainer_id in [ash_id, misty_id] {
+            catch_pokemon(
+                &mock.api(),
+                "pikachu",
+                &CatchOptions::default(),
+                trainer_id,
+                &pool,
+            )
+            .await
+            .expect("Could not catch pikachu");
+        }
+        catch_pokemon(
+            &mock.api(),
+            "eevee",
+            &CatchOptions::default(),
+            misty_id,
+            &pool,
+        )
+        .await
+        .expect("Could not catch eevee");
+        assert!(matches!(
+            info_pokemon("eevee", ash_id, &pool).await,
+            Err(PokeError::NotInCollection(_))
+        ));
+
+        let trainers = list_trainers(&pool).await.expect("Could not list trainers");
+        let caught: Vec<(&str, i64)> = trainers
+            .iter()
+            .map(|t| (t.name.as_str(), t.caught))
+            .collect();
+        assert_eq!(caught, vec![("ash", 1), ("misty", 2)]);
+
+        delete_trainer("misty", &pool)
+            .await
+            .expect("Could not delete misty");
+        assert!(matches!(
+            delete_trainer(DEFAULT_TRAINER, &pool).await,
+            Err(PokeError::Config(_))
+        ));
+        assert!(matches!(
+            trainer_id("misty", &pool).await,
+            Err(PokeError::UnknownTrainer(_))
+        ));
+        assert_eq!(
+            info_pokemon("pikachu", ash_id, &pool)
+                .await
+                .expect("Could not get pikachu")
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_catch_output() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+
+        let pikachu = catch_pokemon(
+            &mock.api(),
+            "pikachu",
+            &CatchOptions::default(),
+            trainer_id,
+            &pool,
+        )
+        .await
+        .expect("Could not catch pikachu");
+
+        let json: Value =
+            serde_json::from_str(&output::render_one(OutputFormat::Json, &pikachu).unwrap())
+                .unwrap();
+        assert_eq!(json["id"], 25);
+        assert_eq!(json["name"], "pikachu");
+        assert_eq!(json["types"], serde_json::json!(["electric"]));
+        assert_eq!(json["stats"][0]["name"], "hp");
+        assert_eq!(json["stats"][0]["base_stat"], 35);
+        assert_eq!(json["instances"][0]["is_shiny"], false);
+
+        let csv = output::render(OutputFormat::Csv, &[pikachu]).unwrap();
+        assert_eq!(
+            csv,
+            "id,name,types,base_experience,hp,attack,defense,special-attack,special-defense,speed,count,shiny_count\n25,pikachu,electric,112,35,55,40,50,50,90,1,0"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stored_sprites() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+
+        catch_pokemon(
+            &mock.api(),
+            "pikachu",
+            &CatchOptions::default(),
+            trainer_id,
+            &pool,
+        )
+        .await
+        .expect("Could not catch pikachu");
+
+        let pikachu = info_pokemon("pikachu", trainer_id, &pool).await.unwrap();
+        let url = pikachu.sprites.front(true).unwrap();
+        assert!(url.ends_with("/pokemon/shiny/25.png"));
+        assert!(pikachu
+            .sprites
+            .artwork
+            .as_deref()
+            .is_some_and(|url| url.ends_with("/official-artwork/25.png")));
+        assert_eq!(pikachu.sprites.urls().len(), 6);
+
+        // Stored image is used before the API
+        sqlx::query("INSERT INTO sprite_image (image_url, image_bytes) VALUES ($1, $2)")
+            .bind(url)
+            .bind(b"png".as_slice())
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(load_sprite(&mock.api(), url, &pool).await.unwrap(), b"png");
+    }
+
+    #[tokio::test]
+    async fn test_export_gallery() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+        let pikachu = catch_pokemon(
+            &mock.api(),
+            "pikachu",
+            &CatchOptions::default(),
+            trainer_id,
+            &pool,
+        )
+        .await
+        .expect("Could not catch pikachu");
+        sqlx::query("INSERT INTO sprite_image (image_url, image_bytes) VALUES ($1, $2)")
+            .bind(pikachu.sprites.front(false))
+            .bind(b"png".as_slice())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let dir = env::temp_dir().join(format!("poke-collect-gallery-test-{}", std::process::id()));
+        export_gallery(&mock.api(), &dir, trainer_id, &pool)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read(dir.join("sprites/25.png")).unwrap(), b"png");
+        let index = std::fs::read_to_string(dir.join("index.html")).unwrap();
+        assert!(index.contains("<img src=\"sprites/25.png\" alt=\"pikachu\">"));
+        assert!(index.contains("<tr><td>speed</td><td>90</td></tr>"));
+        assert!(dir.join("gen-1.html").exists());
+        assert!(dir.join("style.css").exists());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_verify_shiny() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+
+        catch_pokemon(
+            &mock.api(),
+            "pikachu",
+            &CatchOptions::default(),
+            trainer_id,
+            &pool,
+        )
+        .await
+        .expect("Could not catch pikachu");
+        let pikachu = String::from("pikachu");
+        assert!(matches!(
+            verify_shiny(&pikachu, None, trainer_id, &pool).await,
+            Err(PokeError::InvalidShinyProof(_))
+        ));
+
+        let proof = shiny_pokemon(
+            &pikachu,
+            None,
+            Some(Target::repeat(0, 1)),
+            &MineOptions::default(),
+            trainer_id,
+            &pool,
+        )
+        .await
+        .expect("Could not hunt pikachu");
+        let checks = verify_shiny(&pikachu, None, trainer_id, &pool)
+            .await
+            .expect("Could not verify pikachu");
+        assert_eq!(checks.len(), 1);
+        assert!(checks[0].valid);
+        assert_eq!(checks[0].proof, Some(proof));
+
+        // The only pikachu is shiny, its proof is kept unless it is asked for
+        assert!(matches!(
+            shiny_pokemon(
+                &pikachu,
+                None,
+                Some(Target::repeat(0, 1)),
+                &MineOptions::default(),
+                trainer_id,
+                &pool,
+            )
+            .await,
+            Err(PokeError::ShinyHunt(_))
+        ));
+
+        // Claiming a harder target than the one mined
+        sqlx::query("UPDATE poke_instance SET instance_shiny_target='prefix:' || repeat('0', 64)")
+            .execute(&pool)
+            .await
+            .expect("Could not update pikachu");
+        let checks = verify_shiny(&pikachu, None, trainer_id, &pool)
+            .await
+            .expect("Could not verify pikachu");
+        assert!(!checks[0].valid);
+
+        // Shiny without a stored proof
+        sqlx::query("UPDATE poke_instance SET instance_shiny_nonce=NULL")
+            .execute(&pool)
+            .await
+            .expect("Could not update pikachu");
+        let checks = verify_shiny(&pikachu, None, trainer_id, &pool)
+            .await
+            .expect("Could not verify pikachu");
+        assert!(checks[0].proof.is_none() && !checks[0].valid);
+    }
+
+    #[tokio::test]
+    async fn test_shiny_resume() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+
+        catch_pokemon(
+            &mock.api(),
+            "pikachu",
+            &CatchOptions::default(),
+            trainer_id,
+            &pool,
+        )
+        .await
+        .expect("Could not catch pikachu");
+        let pikachu = String::from("pikachu");
+        assert!(matches!(
+            shiny_pokemon(
+                &pikachu,
+                None,
+                None,
+                &MineOptions::default(),
+                trainer_id,
+                &pool
+            )
+            .await,
+            Err(PokeError::NoSavedHunt(_))
+        ));
+
+        // A hunt that cannot succeed is saved when it times out
+        let options = MineOptions {
+            threads: 2,
+            timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+        let res = shiny_pokemon(
+            &pikachu,
+            None,
+            Some(Target::repeat(9, 9)),
+            &options,
+            trainer_id,
+            &pool,
+        )
+        .await;
+        assert!(matches!(res, Err(PokeError::HuntTimeout(_))));
+        let row = sqlx::query("SELECT hunt_frontier, hunt_attempts FROM shiny_hunt")
+            .fetch_one(&pool)
+            .await
+            .expect("Hunt was not saved");
+        let frontier: Vec<i64> = row.get("hunt_frontier");
+        assert_eq!(frontier.len(), 2);
+        assert!(row.get::<i64, _>("hunt_attempts") > 0);
+
+        // Easier saved target, to finish it in the test
+        sqlx::query("UPDATE shiny_hunt SET hunt_target='prefix:9'")
+            .execute(&pool)
+            .await
+            .expect("Could not update the hunt");
+        let proof = shiny_pokemon(&pikachu, None, None, &options, trainer_id, &pool)
+            .await
+            .expect("Could not resume the hunt");
+        assert_eq!(proof.target, Target::repeat(9, 1));
+        assert!(proof.nonce as i64 >= frontier[(proof.nonce % 2) as usize]);
+        assert!(
+            verify_shiny(&pikachu, None, trainer_id, &pool)
+                .await
+                .expect("Could not verify pikachu")[0]
+                .valid
+        );
+        assert!(sqlx::query("SELECT * FROM shiny_hunt")
+            .fetch_optional(&pool)
+            .await
+            .expect("Could not query hunts")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_catch_shiny_encounter() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+
+        // 1 in 1 odds, always shiny
+        let options = CatchOptions {
+            encounter: Some(ShinyEncounter::new(1, false, Some(7))),
+            ..Default::default()
+        };
+        let eevee = catch_pokemon(&mock.api(), "eevee", &options, trainer_id, &pool)
+            .await
+            .expect("Could not catch eevee");
+        assert!(eevee.instances[0].is_shiny);
+        assert_eq!(
+            eevee.instances[0].shiny_source,
+            Some(ShinySource::Encounter)
+        );
+        assert!(eevee.instances[0].shiny_proof.is_none());
+
+        // Nothing to verify for an encounter
+        assert!(matches!(
+            verify_shiny("eevee", None, trainer_id, &pool).await,
+            Err(PokeError::InvalidShinyProof(_))
+        ));
+
+        // Without odds nothing is rolled
+        let pikachu = catch_pokemon(
+            &mock.api(),
+            "pikachu",
+            &CatchOptions::default(),
+            trainer_id,
+            &pool,
+        )
+        .await
+        .expect("Could not catch pikachu");
+        assert!(!pikachu.instances[0].is_shiny);
+        assert_eq!(pikachu.instances[0].shiny_source, None);
+    }
+
+    #[test]
+    fn test_parse_target() {
+        assert!(parse_target_arg("3").is_ok() && parse_target_arg("prefix:00").is_ok());
+        assert!(parse_target_arg("prefix:zz").is_err());
+        assert_eq!(parse_target("3", Some(0)), Ok(Target::repeat(0, 3)));
+        assert!(parse_target("3", None).is_err());
+        assert!(parse_target("65", Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_encounter_from_matches() {
+        let encounter = |args: &[&str]| {
+            let matches = Command::new("catch")
+                .args(encounter_args())
+                .get_matches_from(std::iter::once("catch").chain(args.iter().copied()));
+            encounter_from_matches(&matches)
+        };
+
+        assert!(encounter(&[]).is_none());
+        // A seed alone rolls at the default odds
+        let seeded = encounter(&["--seed", "42"]).expect("Seed should roll");
+        assert_eq!(seeded.chance(), 1.0 / shiny::DEFAULT_SHINY_ODDS as f64);
+        assert!(encounter(&["--shiny-charm"]).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_dex_pokemon() {
+        let (pool, trainer_id, _guard) = setup_test_db().await;
+        let mock = MockPokeApi::start().await;
+
+        for name in ["pikachu", "mew"] {
+            catch_pokemon(
+                &mock.api(),
+                name,
+                &CatchOptions::default(),
+                trainer_id,
+                &pool,
+            )
+            .await
+            .expect("Could not catch pokemon");
+        }
+        sqlx::query("UPDATE poke_instance SET instance_is_shiny=true WHERE poke_id=151")
+            .execute(&pool)
+            .await
+            .expect("Could not update mew");
+
+        let dex = dex_pokemon(
+            &mock.api(),
+            Some(&Generation::Kanto),
+            true,
+            trainer_id,
+            &pool,
+        )
+        .await
+        .expect("Could not get dex");
+        assert_eq!(dex.len(), 1);
+        assert_eq!((dex[0].total, dex[0].caught, dex[0].shiny), (151, 2, 1));
+        assert_eq!(dex[0].missing.len(), 149);
+        assert_eq!(dex[0].missing[0], (1, Some(String::from("bulbasaur"))));
+        assert!(!dex[0].missing.iter().any(|(id, _)| *id == 25 || *id == 151));
+
+        // Whole dex, without names
+        let dex = dex_pokemon(&mock.api(), None, false, trainer_id, &pool)
+            .await
+            .expect("Could not get dex");
+        assert_eq!(dex.len(), 9);
+        assert!(dex.iter().all(|gen| gen.missing.is_empty()));
+        assert_eq!(dex.iter().map(|gen| gen.caught).sum::<i64>(), 2);
+    }
 }
@@ -0,0 +1,252 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, Request, Respond, ResponseTemplate,
+};
+
+use crate::{api::PokeApi, throttle::ApiLimits};
+
+// In-process fake pokeapi serving recorded fixtures, for tests only
+// Fixtures live in tests/fixtures/<resource>/<name>.json and are served on
+// /api/v2/<resource>/<name> and /api/v2/<resource>/<id>
+// tests/fixtures/<resource>.json is the full list of a resource, served
+// paginated (offset and limit) on /api/v2/<resource>
+// Anything else is a 404, like the real API
+// Resources have an ETag and answer 304 to a matching If-None-Match
+
+// Bodies changed by update, by request path
+type Updates = Arc<Mutex<HashMap<String, String>>>;
+
+const API_PATH: &str = "/api/v2/";
+
+pub struct MockPokeApi {
+    server: MockServer,
+    updates: Updates,
+}
+
+impl MockPokeApi {
+    pub async fn start() -> Self {
+        let server = MockServer::start().await;
+        let mock = MockPokeApi {
+            server,
+            updates: Updates::default(),
+        };
+
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures");
+        for resource in fs::read_dir(fixtures).expect("Could not read fixtures directory") {
+            let resource = resource.expect("Could not read fixtures directory");
+            if resource.path().is_dir() {
+                mock.mount_resource(&resource.file_name().to_string_lossy(), resource.path())
+                    .await;
+            } else if resource.path().extension().is_some_and(|ext| ext == "json") {
+                mock.mount_list(resource.path()).await;
+            }
+        }
+
+        mock
+    }
+
+    async fn mount_resource(&self, resource: &str, dir: PathBuf) {
+        for fixture in fs::read_dir(dir).expect("Could not read fixtures directory") {
+            let fixture = fixture.expect("Could not read fixture").path();
+            if fixture.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+
+            let name = fixture.file_stem().unwrap().to_string_lossy().to_string();
+            let body = fs::read_to_string(&fixture).expect("Could not read fixture");
+            let json: Value = serde_json::from_str(&body).expect("Fixture is not valid JSON");
+
+            self.respond(resource, &name, self.fixture(&body)).await;
+            if let Some(id) = json.get("id").and_then(Value::as_u64) {
+                self.respond(resource, &id.to_string(), self.fixture(&body))
+                    .await;
+            }
+        }
+    }
+
+    async fn mount_list(&self, fixture: PathBuf) {
+        let resource = fixture.file_stem().unwrap().to_string_lossy().to_string();
+        let body = fs::read_to_string(&fixture).expect("Could not read fixture");
+        let json: Value = serde_json::from_str(&body).expect("Fixture is not valid JSON");
+        let results = json["results"]
+            .as_array()
+            .expect("List fixture without results")
+            .clone();
+
+        for list_path in [
+            format!("{}{}", API_PATH, resource),
+            format!("{}{}/", API_PATH, resource),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(list_path))
+                .respond_with(ListResponder {
+                    results: results.clone(),
+                })
+                .mount(&self.server)
+                .await;
+        }
+    }
+
+    async fn respond(&self, resource: &str, name: &str, responder: impl Respond + 'static) {
+        Mock::given(method("GET"))
+            .and(path(format!("{}{}/{}", API_PATH, resource, name)))
+            .respond_with(responder)
+            .mount(&self.server)
+            .await;
+    }
+
+    fn fixture(&self, body: &str) -> FixtureResponder {
+        FixtureResponder {
+            body: body.to_string(),
+            updates: self.updates.clone(),
+        }
+    }
+
+    // Upstream change of a resource, served with a new ETag until the next update
+    pub async fn update(&self, resource: &str, name: &str, body: &Value) {
+        self.updates.lock().unwrap().insert(
+            format!("{}{}/{}", API_PATH, resource, name),
+            body.to_string(),
+        );
+    }
+
+    // Override a resource with a 429, optionally with a Retry-After header
+    pub async fn rate_limited(&self, resource: &str, name: &str, retry_after: Option<u64>) {
+        let mut template = ResponseTemplate::new(429);
+        if let Some(retry_after) = retry_after {
+            template = template.insert_header("Retry-After", retry_after.to_string());
+        }
+        self.override_with(resource, name, template).await;
+    }
+
+    // Override a resource with any status (500, 503...)
+    pub async fn fail_with(&self, resource: &str, name: &str, status: u16) {
+        self.override_with(resource, name, ResponseTemplate::new(status))
+            .await;
+    }
+
+    // Same for the first times requests only, the fixture is served afterwards
+    pub async fn fail_times(&self, resource: &str, name: &str, status: u16, times: u64) {
+        Mock::given(method("GET"))
+            .and(path(format!("{}{}/{}", API_PATH, resource, name)))
+            .respond_with(ResponseTemplate::new(status))
+            .up_to_n_times(times)
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    async fn override_with(&self, resource: &str, name: &str, template: ResponseTemplate) {
+        Mock::given(method("GET"))
+            .and(path(format!("{}{}/{}", API_PATH, resource, name)))
+            .respond_with(template)
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("{}{}", self.server.uri(), API_PATH)
+    }
+
+    // Quick retries, and any Retry-After gives up at once so tests never wait
+    pub fn api(&self) -> PokeApi {
+        PokeApi::new(PokeApi::default_client(), &self.base_url()).with_limits(ApiLimits {
+            rate: 0.0,
+            backoff: std::time::Duration::from_millis(1),
+            max_delay: std::time::Duration::from_millis(10),
+            ..Default::default()
+        })
+    }
+
+    // Number of requests the server received, to check caching
+    pub async fn request_count(&self) -> usize {
+        self.server
+            .received_requests()
+            .await
+            .map(|requests| requests.len())
+            .unwrap_or_default()
+    }
+
+    // Requests sent with an If-None-Match, to check revalidation
+    pub async fn conditional_count(&self) -> usize {
+        self.server
+            .received_requests()
+            .await
+            .map(|requests| {
+                requests
+                    .iter()
+                    .filter(|request| request.headers.contains_key("If-None-Match"))
+                    .count()
+            })
+            .unwrap_or_default()
+    }
+}
+
+struct FixtureResponder {
+    body: String,
+    updates: Updates,
+}
+
+impl Respond for FixtureResponder {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let body = self
+            .updates
+            .lock()
+            .unwrap()
+            .get(request.url.path())
+            .cloned()
+            .unwrap_or_else(|| self.body.clone());
+        let etag = format!("\"{:x}\"", Sha256::digest(&body));
+
+        let matches = request
+            .headers
+            .get("If-None-Match")
+            .is_some_and(|value| value.as_bytes() == etag.as_bytes());
+        if matches {
+            return ResponseTemplate::new(304).insert_header("ETag", etag.as_str());
+        }
+        ResponseTemplate::new(200)
+            .insert_header("ETag", etag.as_str())
+            .set_body_raw(body, "application/json")
+    }
+}
+
+// Paginates a list fixture like the API does with offset and limit
+struct ListResponder {
+    results: Vec<Value>,
+}
+
+impl Respond for ListResponder {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let param = |name: &str, default: usize| {
+            request
+                .url
+                .query_pairs()
+                .find(|(key, _)| key == name)
+                .and_then(|(_, value)| value.parse().ok())
+                .unwrap_or(default)
+        };
+        let offset = param("offset", 0).min(self.results.len());
+        let limit = param("limit", 20);
+        let page: Vec<&Value> = self.results.iter().skip(offset).take(limit).collect();
+
+        ResponseTemplate::new(200).set_body_json(json!({
+            "count": self.results.len(),
+            "next": null,
+            "previous": null,
+            "results": page,
+        }))
+    }
+}
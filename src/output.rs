@@ -0,0 +1,182 @@
+use std::fmt::{self, Write};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::error::PokeError;
+
+// Output formats shared by every command
+// text is the human friendly default, the others are for scripts and dashboards
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Csv,
+    Yaml,
+    Table,
+}
+
+// Flat view of a record for csv and table, nested data is summed up or joined
+pub trait Record: Serialize {
+    fn headers() -> Vec<&'static str>;
+    fn row(&self) -> Vec<String>;
+}
+
+// A single record, json and yaml give an object instead of a list
+pub fn render_one<T: Record>(format: OutputFormat, record: &T) -> Result<String, PokeError> {
+    match format {
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(record)?),
+        OutputFormat::Yaml => to_yaml(record),
+        _ => render(format, std::slice::from_ref(record)),
+    }
+}
+
+// Text is rendered by the caller, records are shown as a table
+pub fn render<T: Record>(format: OutputFormat, records: &[T]) -> Result<String, PokeError> {
+    match format {
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(records)?),
+        OutputFormat::Yaml => to_yaml(records),
+        OutputFormat::Csv => to_csv(records),
+        OutputFormat::Text | OutputFormat::Table => Ok(to_table(records)),
+    }
+}
+
+// Text uses the Display impl of each record, other formats go through render
+pub fn print<T: Record + fmt::Display>(
+    format: OutputFormat,
+    records: &[T],
+) -> Result<(), PokeError> {
+    match format {
+        OutputFormat::Text => records.iter().for_each(|record| println!("{}", record)),
+        _ => println!("{}", render(format, records)?),
+    }
+    Ok(())
+}
+
+pub fn print_one<T: Record + fmt::Display>(
+    format: OutputFormat,
+    record: &T,
+) -> Result<(), PokeError> {
+    match format {
+        OutputFormat::Text => println!("{}", record),
+        _ => println!("{}", render_one(format, record)?),
+    }
+    Ok(())
+}
+
+fn to_yaml<T: Serialize + ?Sized>(value: &T) -> Result<String, PokeError> {
+    serde_yaml::to_string(value).map_err(|e| PokeError::Io(std::io::Error::other(e)))
+}
+
+fn to_csv<T: Record>(records: &[T]) -> Result<String, PokeError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let csv_error = |e: csv::Error| PokeError::Io(std::io::Error::other(e));
+
+    writer.write_record(T::headers()).map_err(csv_error)?;
+    for record in records {
+        writer.write_record(record.row()).map_err(csv_error)?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| PokeError::Io(std::io::Error::other(e.to_string())))?;
+    Ok(String::from_utf8_lossy(&bytes).trim_end().to_string())
+}
+
+fn to_table<T: Record>(records: &[T]) -> String {
+    let headers = T::headers();
+    let rows: Vec<Vec<String>> = records.iter().map(|r| r.row()).collect();
+
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    let line = |cells: Vec<String>| -> String {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<String>>()
+            .join(" | ")
+            .trim_end()
+            .to_string()
+    };
+
+    let _ = writeln!(
+        table,
+        "{}",
+        line(headers.iter().map(|h| h.to_string()).collect())
+    );
+    let _ = writeln!(
+        table,
+        "{}",
+        widths
+            .iter()
+            .map(|width| "-".repeat(*width))
+            .collect::<Vec<String>>()
+            .join("-+-")
+    );
+    for row in rows {
+        let _ = writeln!(table, "{}", line(row));
+    }
+
+    table.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Fake {
+        id: i64,
+        name: String,
+    }
+
+    impl Record for Fake {
+        fn headers() -> Vec<&'static str> {
+            vec!["id", "name"]
+        }
+
+        fn row(&self) -> Vec<String> {
+            vec![self.id.to_string(), self.name.clone()]
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let records = vec![
+            Fake {
+                id: 25,
+                name: String::from("pikachu"),
+            },
+            Fake {
+                id: 151,
+                name: String::from("mew, the \"first\""),
+            },
+        ];
+
+        assert_eq!(
+            render(OutputFormat::Table, &records).unwrap(),
+            "id  | name\n----+-----------------\n25  | pikachu\n151 | mew, the \"first\""
+        );
+        assert_eq!(
+            render(OutputFormat::Csv, &records).unwrap(),
+            "id,name\n25,pikachu\n151,\"mew, the \"\"first\"\"\""
+        );
+        assert_eq!(
+            render_one(OutputFormat::Json, &records[0]).unwrap(),
+            "{\n  \"id\": 25,\n  \"name\": \"pikachu\"\n}"
+        );
+        assert_eq!(
+            render(OutputFormat::Yaml, &records[..1]).unwrap(),
+            "- id: 25\n  name: pikachu\n"
+        );
+    }
+}
@@ -1,7 +1,12 @@
 use core::fmt;
 
-use crate::sprite::Sprites;
-use serde::{Deserialize, Serialize};
+use crate::{
+    output::Record,
+    shiny::{ShinyProof, ShinySource},
+    sprite::{DbSprites, Sprites},
+};
+use chrono::{DateTime, Utc};
+use serde::{ser::SerializeSeq, Deserialize, Serialize, Serializer};
 
 // Structs holding pokemon data
 
@@ -85,6 +90,31 @@ pub struct PokemonType {
     type_info: Data,
 }
 
+// Paginated list of a resource (pokemon-species?offset=0&limit=151)
+#[derive(Deserialize)]
+pub struct ResourceList {
+    results: Vec<Data>,
+}
+
+impl ResourceList {
+    // (id, name) of each resource, id is the last part of its url
+    pub fn entries(&self) -> Vec<(i64, String)> {
+        self.results
+            .iter()
+            .filter_map(|d| {
+                let id = d
+                    .url
+                    .trim_end_matches('/')
+                    .rsplit('/')
+                    .next()?
+                    .parse()
+                    .ok()?;
+                Some((id, d.name.clone()))
+            })
+            .collect()
+    }
+}
+
 #[derive(Deserialize)]
 pub struct Pokemon {
     abilities: Vec<Ability>,
@@ -109,13 +139,118 @@ pub struct Pokemon {
     weight: u32,
 }
 
+// Species data, shared by every caught instance
+// Serialized field names are the stable ones used by every output format
+#[derive(Serialize)]
 pub struct DbPoke {
     pub id: i64,
     pub name: String,
+    #[serde(serialize_with = "serialize_types")]
     pub types: Vec<PokemonType>,
     pub base_experience: i64,
+    #[serde(serialize_with = "serialize_stats")]
     pub stats: Vec<Stat>,
+    pub sprites: DbSprites,
+    pub instances: Vec<PokeInstance>,
+}
+
+// One caught pokemon
+#[derive(Serialize)]
+pub struct PokeInstance {
+    pub id: i64,
+    pub caught_at: DateTime<Utc>,
+    pub nickname: Option<String>,
     pub is_shiny: bool,
+    pub shiny_source: Option<ShinySource>,
+    // Winning nonce of the hunt which made it shiny
+    pub shiny_proof: Option<ShinyProof>,
+}
+
+impl DbPoke {
+    pub fn count(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn shiny_count(&self) -> usize {
+        self.instances.iter().filter(|i| i.is_shiny).count()
+    }
+
+    pub fn type_names(&self) -> Vec<&str> {
+        self.types
+            .iter()
+            .map(|t| t.type_info.name.as_str())
+            .collect()
+    }
+
+    pub fn stat(&self, name: &str) -> Option<u32> {
+        self.stats
+            .iter()
+            .find(|s| s.stat.name == name)
+            .map(|s| s.base_stat)
+    }
+}
+
+pub const STAT_NAMES: [&str; 6] = [
+    "hp",
+    "attack",
+    "defense",
+    "special-attack",
+    "special-defense",
+    "speed",
+];
+
+// Only type names, slots are the list order
+fn serialize_types<S: Serializer>(types: &[PokemonType], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_seq(Some(types.len()))?;
+    for t in types {
+        seq.serialize_element(&t.type_info.name)?;
+    }
+    seq.end()
+}
+
+#[derive(Serialize)]
+struct StatOutput<'a> {
+    name: &'a str,
+    base_stat: u32,
+    effort: u32,
+}
+
+fn serialize_stats<S: Serializer>(stats: &[Stat], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_seq(Some(stats.len()))?;
+    for s in stats {
+        seq.serialize_element(&StatOutput {
+            name: &s.stat.name,
+            base_stat: s.base_stat,
+            effort: s.effort,
+        })?;
+    }
+    seq.end()
+}
+
+// One row per species, instances are counted
+impl Record for DbPoke {
+    fn headers() -> Vec<&'static str> {
+        let mut headers = vec!["id", "name", "types", "base_experience"];
+        headers.extend(STAT_NAMES);
+        headers.extend(["count", "shiny_count"]);
+        headers
+    }
+
+    fn row(&self) -> Vec<String> {
+        let mut row = vec![
+            self.id.to_string(),
+            self.name.clone(),
+            self.type_names().join("|"),
+            self.base_experience.to_string(),
+        ];
+        row.extend(
+            STAT_NAMES
+                .iter()
+                .map(|name| self.stat(name).map(|s| s.to_string()).unwrap_or_default()),
+        );
+        row.extend([self.count().to_string(), self.shiny_count().to_string()]);
+        row
+    }
 }
 
 impl fmt::Display for Pokemon {
@@ -150,6 +285,12 @@ impl fmt::Display for Pokemon {
     }
 }
 
+impl Pokemon {
+    pub fn sprites(&self) -> &Sprites {
+        &self.sprites
+    }
+}
+
 impl From<Pokemon> for String {
     fn from(val: Pokemon) -> Self {
         val.name
@@ -163,14 +304,16 @@ impl From<Pokemon> for DbPoke {
             name: val.name,
             types: val.types,
             base_experience: val.base_experience as i64,
+            sprites: DbSprites::from(&val.sprites),
             stats: val.stats,
-            is_shiny: false,
+            instances: Vec::new(),
         }
     }
 }
 
-impl fmt::Display for DbPoke {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl DbPoke {
+    // Species data, without the caught instances
+    pub fn fmt_species(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(
             f,
             "{}\nID : {},\nbase exerperience: {}",
@@ -186,9 +329,72 @@ impl fmt::Display for DbPoke {
         for s in &self.stats {
             writeln!(f, "{} {}", s.stat.name, s.base_stat)?;
         }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DbPoke {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_species(f)?;
+        writeln!(
+            f,
+            "- Caught : {} ({} shiny)",
+            self.count(),
+            self.shiny_count()
+        )?;
+        for i in &self.instances {
+            writeln!(f, "{}", i)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Collection line of a species then its caught instances, same data as DbPoke
+// for the other formats
+#[derive(Serialize)]
+#[serde(transparent)]
+pub struct CollectionEntry(pub DbPoke);
+
+impl fmt::Display for CollectionEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let poke = &self.0;
+        write!(
+            f,
+            "{} x{} ({} shiny)",
+            poke.name,
+            poke.count(),
+            poke.shiny_count()
+        )?;
+        for instance in &poke.instances {
+            write!(f, "\n  {}", instance)?;
+        }
+        Ok(())
+    }
+}
+
+impl Record for CollectionEntry {
+    fn headers() -> Vec<&'static str> {
+        DbPoke::headers()
+    }
 
-        writeln!(f, "- Shiny : {}", self.is_shiny)?;
+    fn row(&self) -> Vec<String> {
+        self.0.row()
+    }
+}
 
+impl fmt::Display for PokeInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.id)?;
+        if let Some(nickname) = &self.nickname {
+            write!(f, " \"{}\"", nickname)?;
+        }
+        write!(f, " caught {}", self.caught_at.format("%Y-%m-%d %H:%M"))?;
+        match (self.is_shiny, self.shiny_source) {
+            (true, Some(source)) => write!(f, " (shiny, {})", source)?,
+            (true, None) => write!(f, " (shiny)")?,
+            _ => (),
+        }
         Ok(())
     }
 }
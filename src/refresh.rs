@@ -0,0 +1,118 @@
+use core::fmt;
+
+use serde::Serialize;
+
+use crate::{error::PokeError, output::Record, poke::DbPoke};
+
+// Result of refreshing each species of a collection
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RefreshStatus {
+    Unchanged,
+    Updated,
+    Failed,
+}
+
+impl RefreshStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RefreshStatus::Unchanged => "unchanged",
+            RefreshStatus::Updated => "updated",
+            RefreshStatus::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct RefreshReport {
+    pub id: i64,
+    pub name: String,
+    pub status: RefreshStatus,
+    // Stored fields which differ from the API
+    pub changes: Vec<&'static str>,
+    pub error: Option<String>,
+}
+
+impl RefreshReport {
+    pub fn new(stored: &DbPoke, result: Result<Vec<&'static str>, PokeError>) -> Self {
+        let (status, changes, error) = match result {
+            Ok(changes) if changes.is_empty() => (RefreshStatus::Unchanged, changes, None),
+            Ok(changes) => (RefreshStatus::Updated, changes, None),
+            Err(e) => (RefreshStatus::Failed, Vec::new(), Some(e.to_string())),
+        };
+        RefreshReport {
+            id: stored.id,
+            name: stored.name.clone(),
+            status,
+            changes,
+            error,
+        }
+    }
+}
+
+// Fields of the poke table which differ, serialized the way they are stored
+pub fn changes(stored: &DbPoke, upstream: &DbPoke) -> Result<Vec<&'static str>, PokeError> {
+    let mut changes = Vec::new();
+    if stored.name != upstream.name {
+        changes.push("name");
+    }
+    if serde_json::to_value(&stored.types)? != serde_json::to_value(&upstream.types)? {
+        changes.push("types");
+    }
+    if stored.base_experience != upstream.base_experience {
+        changes.push("base_experience");
+    }
+    if serde_json::to_value(&stored.stats)? != serde_json::to_value(&upstream.stats)? {
+        changes.push("stats");
+    }
+    if stored.sprites != upstream.sprites {
+        changes.push("sprites");
+    }
+    Ok(changes)
+}
+
+impl fmt::Display for RefreshReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<16} {}", self.name, self.status.as_str())?;
+        if !self.changes.is_empty() {
+            write!(f, " ({})", self.changes.join(", "))?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, " : {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Record for RefreshReport {
+    fn headers() -> Vec<&'static str> {
+        vec!["id", "name", "status", "changes", "error"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.name.clone(),
+            self.status.as_str().to_string(),
+            self.changes.join("|"),
+            self.error.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+// Count of each status
+pub fn summary(reports: &[RefreshReport]) -> String {
+    [
+        RefreshStatus::Updated,
+        RefreshStatus::Unchanged,
+        RefreshStatus::Failed,
+    ]
+    .iter()
+    .filter_map(|status| {
+        let count = reports.iter().filter(|r| r.status == *status).count();
+        (count > 0).then(|| format!("{} {}", count, status.as_str()))
+    })
+    .collect::<Vec<String>>()
+    .join(", ")
+}
@@ -0,0 +1,646 @@
+use core::fmt;
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+use sha2::{digest::Output, Digest, Sha256};
+
+use crate::{error::PokeError, output::Record, target::Target};
+
+// Shiny imitate "blockchain mining" => find a nonce giving a hash which meets a target
+// (leading 0s, see target.rs)
+// The hashed input is bound to one caught pokemon of one trainer, so a nonce
+// found for a pokemon proves nothing for any other
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HuntIdentity {
+    pub trainer_id: i64,
+    pub poke_id: i64,
+    pub instance_id: i64,
+}
+
+impl HuntIdentity {
+    fn prefix(&self) -> String {
+        format!("{}:{}:{}:", self.trainer_id, self.poke_id, self.instance_id)
+    }
+
+    fn input(&self, nonce: u64) -> String {
+        format!("{}{}", self.prefix(), nonce)
+    }
+}
+
+// Winning nonce of a hunt with the target it was found for
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ShinyProof {
+    pub nonce: u64,
+    pub target: Target,
+}
+
+impl ShinyProof {
+    pub fn hash(&self, identity: &HuntIdentity) -> String {
+        generate_hash(identity, self.nonce)
+    }
+
+    pub fn verify(&self, identity: &HuntIdentity) -> bool {
+        self.target.matches_hex(&self.hash(identity))
+    }
+}
+
+pub fn generate_hash(identity: &HuntIdentity, nonce: u64) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(identity.input(nonce));
+    let result = hasher.finalize();
+    format!("{:x}", result)
+}
+
+// Mining side of generate_hash, without any allocation per attempt
+// The identity is hashed once, each attempt only clones that state and adds the nonce
+struct NonceHasher {
+    prefix: Sha256,
+}
+
+impl NonceHasher {
+    fn new(identity: &HuntIdentity) -> Self {
+        let mut prefix = Sha256::new();
+        prefix.update(identity.prefix());
+        NonceHasher { prefix }
+    }
+
+    fn hash(&self, nonce: u64, digest: &mut Output<Sha256>) {
+        let mut buffer = [0u8; 20];
+        let mut hasher = self.prefix.clone();
+        hasher.update(write_decimal(nonce, &mut buffer));
+        hasher.finalize_into(digest);
+    }
+}
+
+// Decimal digits of n at the end of the buffer, u64::MAX has 20 digits
+fn write_decimal(mut n: u64, buffer: &mut [u8; 20]) -> &[u8] {
+    let mut start = buffer.len();
+    loop {
+        start -= 1;
+        buffer[start] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            return &buffer[start..];
+        }
+    }
+}
+
+// How a hunt runs, progress goes to stderr
+// interrupt is set from outside (Ctrl-C) to stop the hunt early
+#[derive(Clone)]
+pub struct MineOptions {
+    pub threads: usize,
+    pub timeout: Option<Duration>,
+    pub progress: bool,
+    pub checkpoint_interval: Duration,
+    pub interrupt: Option<Arc<AtomicBool>>,
+}
+
+impl Default for MineOptions {
+    fn default() -> Self {
+        MineOptions {
+            threads: default_threads(),
+            timeout: None,
+            progress: false,
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+            interrupt: None,
+        }
+    }
+}
+
+pub fn default_threads() -> usize {
+    thread::available_parallelism()
+        .map(|threads| threads.get())
+        .unwrap_or(8)
+}
+
+// Searched nonces of a hunt, worker i tries i, i + threads, i + 2 * threads...
+// frontier[i] is the next nonce of worker i, every nonce below it in its
+// sequence has been tried
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HuntState {
+    pub frontier: Vec<u64>,
+    pub attempts: u64,
+}
+
+impl HuntState {
+    pub fn new(threads: usize) -> Self {
+        HuntState {
+            frontier: (0..threads.max(1) as u64).collect(),
+            attempts: 0,
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.frontier.len()
+    }
+}
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+// Workers only publish their progress and look at the stop flag between batches
+const COUNT_BATCH: u64 = 1024;
+
+// Search a nonce on every thread, from the given state or from scratch
+// checkpoint is called regularly with the searched frontier, and once more
+// when the hunt stops without a shiny (timeout or interrupt)
+pub fn mine(
+    identity: HuntIdentity,
+    target: Target,
+    resume: Option<HuntState>,
+    options: &MineOptions,
+    mut checkpoint: impl FnMut(&HuntState),
+) -> Result<ShinyProof, PokeError> {
+    let state = resume.unwrap_or_else(|| HuntState::new(options.threads));
+    let threads = state.threads();
+    let (tx_result, rx_result) = mpsc::channel();
+    let mut handles = vec![];
+    let stop = Arc::new(AtomicBool::new(false));
+    let attempts = Arc::new(AtomicU64::new(0));
+    let frontier: Arc<Vec<AtomicU64>> =
+        Arc::new(state.frontier.iter().map(|n| AtomicU64::new(*n)).collect());
+
+    for (i, start) in state.frontier.iter().enumerate() {
+        let tx_result = tx_result.clone();
+        let stop = stop.clone();
+        let attempts = attempts.clone();
+        let frontier = frontier.clone();
+        let target = target.clone();
+        let mut counter = *start;
+        let handle = thread::spawn(move || {
+            let hasher = NonceHasher::new(&identity);
+            let mut digest = Output::<Sha256>::default();
+            // Shared state is only touched between batches
+            while !stop.load(Ordering::Relaxed) {
+                for _ in 0..COUNT_BATCH {
+                    hasher.hash(counter, &mut digest);
+                    if target.matches(&digest) {
+                        stop.store(true, Ordering::Relaxed);
+                        tx_result
+                            .send(counter)
+                            .expect("Could not send result for shiny hunt");
+                        return;
+                    }
+                    counter += threads as u64;
+                }
+                attempts.fetch_add(COUNT_BATCH, Ordering::Relaxed);
+                frontier[i].store(counter, Ordering::Relaxed);
+            }
+        });
+        handles.push(handle);
+    }
+
+    drop(tx_result);
+
+    let snapshot = || HuntState {
+        frontier: frontier.iter().map(|n| n.load(Ordering::Relaxed)).collect(),
+        attempts: state.attempts + attempts.load(Ordering::Relaxed),
+    };
+
+    let expected = target.expected_attempts();
+    if options.progress {
+        eprintln!(
+            "Hunting {} with {} threads, {:.0} hashes expected",
+            target, threads, expected
+        );
+        if state.attempts > 0 {
+            eprintln!("Resuming after {} hashes", state.attempts);
+        }
+    }
+
+    let start = Instant::now();
+    let mut last_checkpoint = Instant::now();
+    let result = loop {
+        let wait = match options.timeout {
+            Some(timeout) => timeout
+                .saturating_sub(start.elapsed())
+                .min(PROGRESS_INTERVAL),
+            None => PROGRESS_INTERVAL,
+        };
+        match rx_result.recv_timeout(wait) {
+            Ok(nonce) => break Ok(nonce),
+            Err(RecvTimeoutError::Timeout) => {
+                if options.progress {
+                    let session = attempts.load(Ordering::Relaxed);
+                    print_progress(state.attempts + session, session, start.elapsed(), expected);
+                }
+                if let Some(timeout) = options.timeout {
+                    if start.elapsed() >= timeout {
+                        break Err(PokeError::HuntTimeout(timeout.as_secs()));
+                    }
+                }
+                if options
+                    .interrupt
+                    .as_ref()
+                    .is_some_and(|interrupt| interrupt.load(Ordering::SeqCst))
+                {
+                    break Err(PokeError::HuntInterrupted);
+                }
+                if last_checkpoint.elapsed() >= options.checkpoint_interval {
+                    checkpoint(&snapshot());
+                    last_checkpoint = Instant::now();
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                break Err(PokeError::ShinyHunt(
+                    "every worker stopped without a result".to_string(),
+                ))
+            }
+        }
+    };
+
+    stop.store(true, Ordering::SeqCst);
+    for handle in handles {
+        handle.join().expect("Could not join thread");
+    }
+    if options.progress {
+        eprintln!();
+    }
+
+    // Workers are stopped, the frontier is exact
+    if result.is_err() {
+        checkpoint(&snapshot());
+    }
+
+    Ok(ShinyProof {
+        nonce: result?,
+        target,
+    })
+}
+
+// Total attempts include the resumed ones, the rate only counts this run
+fn print_progress(total: u64, attempts: u64, elapsed: Duration, expected: f64) {
+    let rate = attempts as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+    eprint!(
+        "\r{} hashes in {}s ({:.0} H/s, {:.1}% of expected)",
+        total,
+        elapsed.as_secs(),
+        rate,
+        total as f64 / expected * 100.0
+    );
+}
+
+// How an instance became shiny
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShinySource {
+    Hunt,
+    Encounter,
+}
+
+impl ShinySource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShinySource::Hunt => "hunt",
+            ShinySource::Encounter => "encounter",
+        }
+    }
+}
+
+impl FromStr for ShinySource {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "hunt" => Ok(ShinySource::Hunt),
+            "encounter" => Ok(ShinySource::Encounter),
+            _ => Err(format!("Unknown shiny source {}", input)),
+        }
+    }
+}
+
+impl fmt::Display for ShinySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// Random shiny roll when catching, like the games
+// Each roll has 1 chance out of odds, the shiny charm gives 2 more rolls
+// so the chance is 1 - (1 - 1/odds)^rolls (about 1/1365 with the charm at 1/4096)
+// A seed gives the same rolls on every run, for tests and replays
+
+pub const DEFAULT_SHINY_ODDS: u32 = 4096;
+const SHINY_CHARM_ROLLS: u32 = 3;
+
+#[derive(Clone)]
+pub struct ShinyEncounter {
+    odds: u32,
+    rolls: u32,
+    rng: Arc<Mutex<ChaCha8Rng>>,
+}
+
+impl ShinyEncounter {
+    pub fn new(odds: u32, charm: bool, seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+            None => ChaCha8Rng::from_entropy(),
+        };
+        ShinyEncounter {
+            odds: odds.max(1),
+            rolls: if charm { SHINY_CHARM_ROLLS } else { 1 },
+            rng: Arc::new(Mutex::new(rng)),
+        }
+    }
+
+    pub fn roll(&self) -> bool {
+        let mut rng = self.rng.lock().expect("Shiny rng lock poisoned");
+        (0..self.rolls).any(|_| rng.gen_range(0..self.odds) == 0)
+    }
+
+    // Independent encounter seeded from this one, so concurrent catches stay
+    // reproducible whatever order they finish in
+    pub fn fork(&self) -> Self {
+        let mut rng = self.rng.lock().expect("Shiny rng lock poisoned");
+        ShinyEncounter {
+            odds: self.odds,
+            rolls: self.rolls,
+            rng: Arc::new(Mutex::new(ChaCha8Rng::seed_from_u64(rng.gen()))),
+        }
+    }
+
+    pub fn chance(&self) -> f64 {
+        1.0 - (1.0 - 1.0 / self.odds as f64).powi(self.rolls as i32)
+    }
+}
+
+// Hash rate of the mining loop, to track performance regressions
+#[derive(Serialize)]
+pub struct ShinyBench {
+    pub threads: usize,
+    pub hashes: u64,
+    pub seconds: f64,
+    pub hashes_per_sec: f64,
+}
+
+// Mine a target that is never reached for the given duration
+pub fn bench(threads: usize, duration: Duration) -> Result<ShinyBench, PokeError> {
+    let identity = HuntIdentity {
+        trainer_id: 0,
+        poke_id: 0,
+        instance_id: 0,
+    };
+    let options = MineOptions {
+        threads,
+        timeout: Some(duration),
+        ..Default::default()
+    };
+
+    let start = Instant::now();
+    let mut last_state = None;
+    match mine(identity, Target::repeat(0, 64), None, &options, |state| {
+        last_state = Some(state.clone())
+    }) {
+        Err(PokeError::HuntTimeout(_)) => (),
+        Err(e) => return Err(e),
+        Ok(_) => return Err(PokeError::ShinyHunt("bench target was reached".to_string())),
+    }
+    let seconds = start.elapsed().as_secs_f64();
+    let hashes = last_state.map(|state| state.attempts).unwrap_or_default();
+
+    Ok(ShinyBench {
+        threads: threads.max(1),
+        hashes,
+        seconds,
+        hashes_per_sec: hashes as f64 / seconds,
+    })
+}
+
+impl fmt::Display for ShinyBench {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} hashes in {:.1}s on {} threads : {:.0} H/s",
+            self.hashes, self.seconds, self.threads, self.hashes_per_sec
+        )
+    }
+}
+
+impl Record for ShinyBench {
+    fn headers() -> Vec<&'static str> {
+        vec!["threads", "hashes", "seconds", "hashes_per_sec"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.threads.to_string(),
+            self.hashes.to_string(),
+            format!("{:.3}", self.seconds),
+            format!("{:.0}", self.hashes_per_sec),
+        ]
+    }
+}
+
+// Result of verify-shiny for one caught pokemon
+#[derive(Serialize)]
+pub struct ShinyCheck {
+    pub instance_id: i64,
+    pub name: String,
+    pub proof: Option<ShinyProof>,
+    pub hash: Option<String>,
+    pub valid: bool,
+}
+
+impl ShinyCheck {
+    pub fn new(name: String, identity: &HuntIdentity, proof: Option<ShinyProof>) -> Self {
+        let hash = proof.as_ref().map(|proof| proof.hash(identity));
+        let valid = proof.as_ref().is_some_and(|proof| proof.verify(identity));
+        ShinyCheck {
+            instance_id: identity.instance_id,
+            name,
+            proof,
+            hash,
+            valid,
+        }
+    }
+}
+
+impl fmt::Display for ShinyCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} #{} : ", self.name, self.instance_id)?;
+        match (&self.proof, &self.hash) {
+            (Some(proof), Some(hash)) => write!(
+                f,
+                "{} proof (nonce {}, {}, hash {})",
+                if self.valid { "valid" } else { "invalid" },
+                proof.nonce,
+                proof.target,
+                hash
+            ),
+            _ => write!(f, "no proof (shiny before proofs were stored)"),
+        }
+    }
+}
+
+impl Record for ShinyCheck {
+    fn headers() -> Vec<&'static str> {
+        vec!["instance_id", "name", "nonce", "target", "hash", "valid"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        let proof =
+            |field: fn(&ShinyProof) -> String| self.proof.as_ref().map(field).unwrap_or_default();
+        vec![
+            self.instance_id.to_string(),
+            self.name.clone(),
+            proof(|p| p.nonce.to_string()),
+            proof(|p| p.target.to_string()),
+            self.hash.clone().unwrap_or_default(),
+            self.valid.to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proof_bound_to_identity() {
+        let pikachu = HuntIdentity {
+            trainer_id: 1,
+            poke_id: 25,
+            instance_id: 1,
+        };
+        let proof = mine(
+            pikachu,
+            Target::repeat(0, 2),
+            None,
+            &MineOptions::default(),
+            |_| (),
+        )
+        .expect("Could not mine");
+        assert!(proof.verify(&pikachu));
+        assert!(proof.hash(&pikachu).starts_with("00"));
+
+        // Same nonce, another pokemon, another trainer or another instance
+        for other in [
+            HuntIdentity {
+                poke_id: 151,
+                ..pikachu
+            },
+            HuntIdentity {
+                trainer_id: 2,
+                ..pikachu
+            },
+            HuntIdentity {
+                instance_id: 2,
+                ..pikachu
+            },
+        ] {
+            assert_ne!(proof.hash(&pikachu), proof.hash(&other));
+        }
+
+        // Claiming a harder target than the one found
+        let tampered = ShinyProof {
+            target: Target::repeat(0, 64),
+            ..proof
+        };
+        assert!(!tampered.verify(&pikachu));
+    }
+
+    #[test]
+    fn test_encounter_roll() {
+        let rolls =
+            |encounter: ShinyEncounter| (0..1000).map(|_| encounter.roll()).collect::<Vec<bool>>();
+
+        // Same seed, same shinies
+        let first = rolls(ShinyEncounter::new(16, false, Some(42)));
+        assert_eq!(first, rolls(ShinyEncounter::new(16, false, Some(42))));
+        assert_ne!(first, rolls(ShinyEncounter::new(16, false, Some(43))));
+        let shinies = first.iter().filter(|shiny| **shiny).count();
+        assert!((30..100).contains(&shinies), "{}", shinies);
+
+        // The charm gives more shinies
+        let charm = rolls(ShinyEncounter::new(16, true, Some(42)));
+        assert!(charm.iter().filter(|shiny| **shiny).count() > shinies);
+
+        assert!(rolls(ShinyEncounter::new(1, false, None))
+            .iter()
+            .all(|shiny| *shiny));
+        assert!((ShinyEncounter::new(4096, true, None).chance() - 1.0 / 1365.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fast_hash_matches_hex() {
+        let identity = HuntIdentity {
+            trainer_id: 3,
+            poke_id: 151,
+            instance_id: 42,
+        };
+        let hasher = NonceHasher::new(&identity);
+        let mut digest = Output::<Sha256>::default();
+
+        for nonce in (0..2000).chain([9, 10, 99, 100, u64::MAX]) {
+            hasher.hash(nonce, &mut digest);
+            let hash = generate_hash(&identity, nonce);
+            assert_eq!(format!("{:x}", digest), hash);
+            for target in [
+                "prefix:0",
+                "prefix:7",
+                "prefix:a3",
+                "suffix:f",
+                "suffix:1c",
+                "below:0.5",
+                "below:1",
+                "below:1.7",
+            ] {
+                let target: Target = target.parse().unwrap();
+                assert_eq!(target.matches(&digest), target.matches_hex(&hash));
+            }
+        }
+    }
+
+    #[test]
+    fn test_mine_timeout_and_resume() {
+        let identity = HuntIdentity {
+            trainer_id: 1,
+            poke_id: 25,
+            instance_id: 1,
+        };
+        let options = MineOptions {
+            threads: 2,
+            timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+
+        // 64 times the same digit never happens, the last checkpoint is kept
+        let mut saved = None;
+        assert!(matches!(
+            mine(identity, Target::repeat(1, 64), None, &options, |state| {
+                saved = Some(state.clone())
+            }),
+            Err(PokeError::HuntTimeout(_))
+        ));
+        let saved = saved.expect("No checkpoint after timeout");
+        assert_eq!(saved.threads(), 2);
+        assert!(saved.attempts > 0);
+        for (i, next) in saved.frontier.iter().enumerate() {
+            assert_eq!(*next % 2, i as u64);
+        }
+
+        // Resumed workers only try nonces after their frontier
+        let proof = mine(
+            identity,
+            Target::repeat(1, 1),
+            Some(saved.clone()),
+            &options,
+            |_| (),
+        )
+        .expect("Could not resume");
+        assert!(proof.verify(&identity));
+        assert!(proof.nonce >= saved.frontier[(proof.nonce % 2) as usize]);
+    }
+}
@@ -1,4 +1,9 @@
-use serde::Deserialize;
+use core::fmt;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::output::Record;
 
 // Struct holding pokemon data for sprites
 
@@ -16,6 +21,451 @@ pub struct Sprites {
     versions: Option<Versions>,
 }
 
+// Which sprite of a set is wanted, front default when nothing is set
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SpriteQuery {
+    pub back: bool,
+    pub shiny: bool,
+    pub female: bool,
+}
+
+impl SpriteQuery {
+    pub fn front(shiny: bool) -> Self {
+        SpriteQuery {
+            shiny,
+            ..Default::default()
+        }
+    }
+
+    // Field name in pokeapi data : front_default, back_shiny, front_shiny_female...
+    fn key(&self) -> String {
+        let side = if self.back { "back" } else { "front" };
+        match (self.shiny, self.female) {
+            (false, false) => format!("{}_default", side),
+            (true, false) => format!("{}_shiny", side),
+            (false, true) => format!("{}_female", side),
+            (true, true) => format!("{}_shiny_female", side),
+        }
+    }
+
+    // Asked sprite first, then drop female (only species which differ have one),
+    // then back (some games have no back sprites), then shiny (generation I has none)
+    fn fallbacks(&self) -> Vec<SpriteQuery> {
+        let mut queries = vec![*self];
+        for query in [
+            SpriteQuery {
+                female: false,
+                ..*self
+            },
+            SpriteQuery {
+                female: false,
+                back: false,
+                ..*self
+            },
+            SpriteQuery {
+                female: false,
+                shiny: false,
+                ..*self
+            },
+            SpriteQuery::default(),
+        ] {
+            if !queries.contains(&query) {
+                queries.push(query);
+            }
+        }
+        queries
+    }
+}
+
+// Sprite sets, names are the pokeapi keys
+// Without a version the main sprites of the pokemon are used
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SpriteVersion {
+    RedBlue,
+    Yellow,
+    Crystal,
+    Gold,
+    Silver,
+    RubySapphire,
+    Emerald,
+    FireredLeafgreen,
+    DiamondPearl,
+    Platinum,
+    HeartgoldSoulsilver,
+    BlackWhite,
+    BlackWhiteAnimated,
+    #[value(name = "x-y")]
+    XY,
+    OmegarubyAlphasapphire,
+    UltraSunUltraMoon,
+    Icons,
+    DreamWorld,
+    Home,
+    OfficialArtwork,
+    Showdown,
+}
+
+impl fmt::Display for SpriteVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_possible_value() {
+            Some(value) => write!(f, "{}", value.get_name()),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+// Chosen sprite, the query may differ from the asked one after fallbacks
+pub struct Sprite<'a> {
+    pub url: &'a str,
+    pub query: SpriteQuery,
+}
+
+impl Sprites {
+    // Sprite of a version (or the main one), following SpriteQuery fallbacks
+    pub fn find(&self, version: Option<SpriteVersion>, query: SpriteQuery) -> Option<Sprite<'_>> {
+        let set = match version {
+            Some(version) => self.version(version)?,
+            None => self as &dyn SpriteSet,
+        };
+        query
+            .fallbacks()
+            .into_iter()
+            .find_map(|query| set.lookup(&query.key()).map(|url| Sprite { url, query }))
+    }
+
+    fn version(&self, version: SpriteVersion) -> Option<&dyn SpriteSet> {
+        let other = self.other.as_ref();
+        let versions = self.versions.as_ref();
+        let gen_i = versions.and_then(|v| v.generation_i.as_ref());
+        let gen_ii = versions.and_then(|v| v.generation_ii.as_ref());
+        let gen_iii = versions.and_then(|v| v.generation_iii.as_ref());
+        let gen_iv = versions.and_then(|v| v.generation_iv.as_ref());
+        let gen_v = versions.and_then(|v| v.generation_v.as_ref());
+        let gen_vi = versions.and_then(|v| v.generation_vi.as_ref());
+        let gen_vii = versions.and_then(|v| v.generation_vii.as_ref());
+        let gen_viii = versions.and_then(|v| v.generation_viii.as_ref());
+
+        fn set<T: SpriteSet>(set: Option<&T>) -> Option<&dyn SpriteSet> {
+            set.map(|set| set as &dyn SpriteSet)
+        }
+
+        match version {
+            SpriteVersion::RedBlue => set(gen_i?.red_blue.as_ref()),
+            SpriteVersion::Yellow => set(gen_i?.yellow.as_ref()),
+            SpriteVersion::Crystal => set(gen_ii?.crystal.as_ref()),
+            SpriteVersion::Gold => set(gen_ii?.gold.as_ref()),
+            SpriteVersion::Silver => set(gen_ii?.silver.as_ref()),
+            SpriteVersion::RubySapphire => set(gen_iii?.ruby_sapphire.as_ref()),
+            SpriteVersion::Emerald => set(gen_iii?.emerald.as_ref()),
+            SpriteVersion::FireredLeafgreen => set(gen_iii?.firered_leafgreen.as_ref()),
+            SpriteVersion::DiamondPearl => set(gen_iv?.diamond_pearl.as_ref()),
+            SpriteVersion::Platinum => set(gen_iv?.platinum.as_ref()),
+            SpriteVersion::HeartgoldSoulsilver => set(gen_iv?.heartgold_soulsilver.as_ref()),
+            SpriteVersion::BlackWhite => set(gen_v?.black_white.as_ref()),
+            SpriteVersion::BlackWhiteAnimated => {
+                set(gen_v?.black_white.as_ref()?.animated.as_ref())
+            }
+            SpriteVersion::XY => set(gen_vi?.x_y.as_ref()),
+            SpriteVersion::OmegarubyAlphasapphire => set(gen_vi?.omegaruby_alphasapphire.as_ref()),
+            SpriteVersion::UltraSunUltraMoon => set(gen_vii?.ultra_sun_ultra_moon.as_ref()),
+            // Generation VIII icons are the most recent ones
+            SpriteVersion::Icons => set(gen_viii
+                .and_then(|gen| gen.icons.as_ref())
+                .or(gen_vii.and_then(|gen| gen.icons.as_ref()))),
+            SpriteVersion::DreamWorld => set(other?.dream_world.as_ref()),
+            SpriteVersion::Home => set(other?.home.as_ref()),
+            SpriteVersion::OfficialArtwork => set(other?.official_artwork.as_ref()),
+            SpriteVersion::Showdown => set(other?.showdown.as_ref()),
+        }
+    }
+}
+
+// Every sprite set has a subset of the same fields, looked up by name
+trait SpriteSet {
+    fn field(&self, key: &str) -> Option<&str>;
+
+    // Generation I and II also have sprites without the white background, they are preferred
+    // front_default -> front_transparent, front_shiny -> front_shiny_transparent
+    fn lookup(&self, key: &str) -> Option<&str> {
+        let transparent = format!("{}_transparent", key.trim_end_matches("_default"));
+        self.field(&transparent).or_else(|| self.field(key))
+    }
+}
+
+macro_rules! sprite_set {
+    ($set:ty { $($field:ident),* $(,)? }) => {
+        impl SpriteSet for $set {
+            fn field(&self, key: &str) -> Option<&str> {
+                match key {
+                    $(stringify!($field) => self.$field.as_deref(),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+sprite_set!(Sprites {
+    back_default,
+    back_female,
+    back_shiny,
+    back_shiny_female,
+    front_default,
+    front_female,
+    front_shiny,
+    front_shiny_female,
+});
+sprite_set!(DreamWorld {
+    front_default,
+    front_female
+});
+sprite_set!(Home {
+    front_default,
+    front_female,
+    front_shiny,
+    front_shiny_female,
+});
+sprite_set!(OfficialArtwork {
+    front_default,
+    front_shiny
+});
+sprite_set!(Showdown {
+    back_default,
+    back_female,
+    back_shiny,
+    back_shiny_female,
+    front_default,
+    front_female,
+    front_shiny,
+    front_shiny_female,
+});
+sprite_set!(RedBlue {
+    back_default,
+    back_transparent,
+    front_default,
+    front_transparent,
+});
+sprite_set!(Yellow {
+    back_default,
+    back_transparent,
+    front_default,
+    front_transparent,
+});
+sprite_set!(Crystal {
+    back_default,
+    back_shiny,
+    back_shiny_transparent,
+    back_transparent,
+    front_default,
+    front_shiny,
+    front_shiny_transparent,
+    front_transparent,
+});
+sprite_set!(Gold {
+    back_default,
+    back_shiny,
+    front_default,
+    front_shiny,
+    front_transparent,
+});
+sprite_set!(Silver {
+    back_default,
+    back_shiny,
+    front_default,
+    front_shiny,
+    front_transparent,
+});
+sprite_set!(Emerald {
+    front_default,
+    front_shiny
+});
+sprite_set!(FireRedLeafGreen {
+    back_default,
+    back_shiny,
+    front_default,
+    front_shiny,
+});
+sprite_set!(RubySapphire {
+    back_default,
+    back_shiny,
+    front_default,
+    front_shiny,
+});
+sprite_set!(DiamondPearl {
+    back_default,
+    back_female,
+    back_shiny,
+    back_shiny_female,
+    front_default,
+    front_female,
+    front_shiny,
+    front_shiny_female,
+});
+sprite_set!(HeartGoldSoulSilver {
+    back_default,
+    back_female,
+    back_shiny,
+    back_shiny_female,
+    front_default,
+    front_female,
+    front_shiny,
+    front_shiny_female,
+});
+sprite_set!(Platinum {
+    back_default,
+    back_female,
+    back_shiny,
+    back_shiny_female,
+    front_default,
+    front_female,
+    front_shiny,
+    front_shiny_female,
+});
+sprite_set!(BlackWhite {
+    back_default,
+    back_female,
+    back_shiny,
+    back_shiny_female,
+    front_default,
+    front_female,
+    front_shiny,
+    front_shiny_female,
+});
+sprite_set!(Animated {
+    back_default,
+    back_female,
+    back_shiny,
+    back_shiny_female,
+    front_default,
+    front_female,
+    front_shiny,
+    front_shiny_female,
+});
+sprite_set!(OmegaRubyAlphaSapphire {
+    front_default,
+    front_female,
+    front_shiny,
+    front_shiny_female,
+});
+sprite_set!(XY {
+    front_default,
+    front_female,
+    front_shiny,
+    front_shiny_female,
+});
+sprite_set!(Icons {
+    front_default,
+    front_female
+});
+sprite_set!(UltraSunUltraMoon {
+    front_default,
+    front_female,
+    front_shiny,
+    front_shiny_female,
+});
+
+// Sprites kept with the species in the database, so the collection does not
+// need the API to show them
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DbSprites {
+    pub front_default: Option<String>,
+    pub front_shiny: Option<String>,
+    pub back_default: Option<String>,
+    pub back_shiny: Option<String>,
+    pub artwork: Option<String>,
+    pub artwork_shiny: Option<String>,
+}
+
+impl DbSprites {
+    // Shiny falls back to the default sprite, some forms have none
+    pub fn front(&self, shiny: bool) -> Option<&str> {
+        let shiny = if shiny {
+            self.front_shiny.as_deref()
+        } else {
+            None
+        };
+        shiny.or(self.front_default.as_deref())
+    }
+
+    pub fn urls(&self) -> Vec<&str> {
+        [
+            &self.front_default,
+            &self.front_shiny,
+            &self.back_default,
+            &self.back_shiny,
+            &self.artwork,
+            &self.artwork_shiny,
+        ]
+        .into_iter()
+        .filter_map(|url| url.as_deref())
+        .collect()
+    }
+}
+
+impl From<&Sprites> for DbSprites {
+    fn from(sprites: &Sprites) -> Self {
+        let artwork = sprites
+            .other
+            .as_ref()
+            .and_then(|other| other.official_artwork.as_ref());
+        DbSprites {
+            front_default: sprites.front_default.clone(),
+            front_shiny: sprites.front_shiny.clone(),
+            back_default: sprites.back_default.clone(),
+            back_shiny: sprites.back_shiny.clone(),
+            artwork: artwork.and_then(|artwork| artwork.front_default.clone()),
+            artwork_shiny: artwork.and_then(|artwork| artwork.front_shiny.clone()),
+        }
+    }
+}
+
+// What the sprite command picked, for scripts
+#[derive(Serialize)]
+pub struct SpriteInfo {
+    pub name: String,
+    pub version: Option<String>,
+    pub url: String,
+    pub back: bool,
+    pub shiny: bool,
+    pub female: bool,
+}
+
+impl SpriteInfo {
+    pub fn new(name: &str, version: Option<SpriteVersion>, sprite: &Sprite) -> Self {
+        SpriteInfo {
+            name: name.to_string(),
+            version: version.map(|version| version.to_string()),
+            url: sprite.url.to_string(),
+            back: sprite.query.back,
+            shiny: sprite.query.shiny,
+            female: sprite.query.female,
+        }
+    }
+}
+
+impl fmt::Display for SpriteInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.url)
+    }
+}
+
+impl Record for SpriteInfo {
+    fn headers() -> Vec<&'static str> {
+        vec!["name", "version", "url", "back", "shiny", "female"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.version.clone().unwrap_or_default(),
+            self.url.clone(),
+            self.back.to_string(),
+            self.shiny.to_string(),
+            self.female.to_string(),
+        ]
+    }
+}
+
 #[derive(Deserialize)]
 struct Other {
     dream_world: Option<DreamWorld>,
@@ -297,3 +747,56 @@ struct UltraSunUltraMoon {
 struct GenerationVIII {
     icons: Option<Icons>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprites(name: &str) -> Sprites {
+        let path = format!(
+            "{}/tests/fixtures/pokemon/{}.json",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        serde_json::from_value(json["sprites"].clone()).unwrap()
+    }
+
+    #[test]
+    fn test_find() {
+        let pikachu = sprites("pikachu");
+        let query = SpriteQuery {
+            back: true,
+            shiny: true,
+            female: true,
+        };
+
+        // No female sprite in the fixture, falls back to back shiny
+        let sprite = pikachu.find(Some(SpriteVersion::Crystal), query).unwrap();
+        assert_eq!(
+            sprite.query,
+            SpriteQuery {
+                female: false,
+                ..query
+            }
+        );
+        assert!(sprite
+            .url
+            .ends_with("crystal/transparent/back/shiny/25.png"));
+
+        // Red and blue have no shiny, official artwork has no back
+        let sprite = pikachu
+            .find(Some(SpriteVersion::RedBlue), SpriteQuery::front(true))
+            .unwrap();
+        assert_eq!(sprite.query, SpriteQuery::default());
+        assert!(sprite.url.ends_with("red-blue/transparent/25.png"));
+        let sprite = pikachu
+            .find(Some(SpriteVersion::OfficialArtwork), query)
+            .unwrap();
+        assert!(sprite.url.ends_with("official-artwork/shiny/25.png"));
+        assert!(pikachu.find(Some(SpriteVersion::Emerald), query).is_none());
+
+        assert_eq!(SpriteVersion::XY.to_string(), "x-y");
+    }
+}
@@ -0,0 +1,234 @@
+use core::fmt;
+use std::str::FromStr;
+
+use serde::{Serialize, Serializer};
+
+// What a shiny hash must look like, written as a spec string :
+// prefix:<hex>   the hex hash starts with these digits (prefix:000 is the old "3 times 0")
+// suffix:<hex>   the hex hash ends with these digits
+// below:<d>      Bitcoin style, the hash read as a 256 bits number is below
+//                2^256 / 16^d, d can be fractional (below:4.5)
+//
+// Expected attempts (average number of hashes to find a shiny) :
+// prefix and suffix : each hex digit matches 1 time out of 16, so 16^len
+// below             : a hash is below the threshold with probability
+//                     threshold / 2^256 = 16^-d, so 16^d
+// below:d is as hard as a prefix of d zeros, and allows anything in between
+
+const HASH_NIBBLES: usize = 64;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    Prefix(Vec<u8>),
+    Suffix(Vec<u8>),
+    Below {
+        difficulty: f64,
+        threshold: [u8; 32],
+    },
+}
+
+impl Target {
+    // Old style target, count times the same hex digit at the start
+    pub fn repeat(digit: u8, count: usize) -> Target {
+        Target::Prefix(vec![digit; count])
+    }
+
+    pub fn below(difficulty: f64) -> Target {
+        Target::Below {
+            difficulty,
+            threshold: threshold(difficulty),
+        }
+    }
+
+    pub fn expected_attempts(&self) -> f64 {
+        match self {
+            Target::Prefix(nibbles) | Target::Suffix(nibbles) => 16f64.powi(nibbles.len() as i32),
+            Target::Below { difficulty, .. } => 16f64.powf(*difficulty),
+        }
+    }
+
+    // Check on the raw digest, used while mining
+    pub fn matches(&self, digest: &[u8]) -> bool {
+        let nibble = |k: usize| {
+            let byte = digest[k / 2];
+            if k.is_multiple_of(2) {
+                byte >> 4
+            } else {
+                byte & 0x0f
+            }
+        };
+        let total = digest.len() * 2;
+
+        match self {
+            Target::Prefix(nibbles) => {
+                nibbles.len() <= total && nibbles.iter().enumerate().all(|(k, n)| nibble(k) == *n)
+            }
+            Target::Suffix(nibbles) => {
+                nibbles.len() <= total
+                    && nibbles
+                        .iter()
+                        .enumerate()
+                        .all(|(k, n)| nibble(total - nibbles.len() + k) == *n)
+            }
+            Target::Below { threshold, .. } => digest < threshold.as_slice(),
+        }
+    }
+
+    // Same check on the hex string of the hash, used to verify proofs
+    pub fn matches_hex(&self, hash: &str) -> bool {
+        match self {
+            Target::Prefix(nibbles) => hash.starts_with(&to_hex(nibbles)),
+            Target::Suffix(nibbles) => hash.ends_with(&to_hex(nibbles)),
+            Target::Below { threshold, .. } => {
+                hash.len() == HASH_NIBBLES && hash < to_hex_bytes(threshold).as_str()
+            }
+        }
+    }
+}
+
+// 2^256 / 16^d as a 256 bits big endian number, 4 * d bits under the top
+// The fractional part of the exponent goes in the 64 bits mantissa
+fn threshold(difficulty: f64) -> [u8; 32] {
+    let mut threshold = [0u8; 32];
+    let bits = 4.0 * difficulty;
+    let whole = bits.floor();
+    // 2^64 * 2^-fraction is in ]2^63, 2^64]
+    let mantissa = (2f64.powf(64.0 - (bits - whole))) as u128;
+
+    // mantissa * 2^(256 - whole - 64), bits past either end are dropped
+    let shift = 256 - whole as i64 - 64;
+    for bit in 0..65 {
+        let position = shift + bit;
+        if mantissa >> bit & 1 == 1 && (0..256).contains(&position) {
+            let position = position as usize;
+            threshold[31 - position / 8] |= 1 << (position % 8);
+        }
+    }
+    threshold
+}
+
+fn to_hex(nibbles: &[u8]) -> String {
+    nibbles.iter().map(|n| format!("{:x}", n)).collect()
+}
+
+fn to_hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hex(input: &str) -> Result<Vec<u8>, String> {
+    if input.is_empty() || input.len() > HASH_NIBBLES {
+        return Err(format!(
+            "Between 1 and {} hex digits expected, got {}",
+            HASH_NIBBLES, input
+        ));
+    }
+    input
+        .chars()
+        .map(|c| {
+            c.to_digit(16)
+                .map(|n| n as u8)
+                .ok_or_else(|| format!("{} is not a hex digit", c))
+        })
+        .collect()
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = input.trim().split_once(':').ok_or_else(|| {
+            format!(
+                "Unknown target {}, use prefix:<hex>, suffix:<hex> or below:<difficulty>",
+                input
+            )
+        })?;
+
+        match kind.to_lowercase().as_str() {
+            "prefix" => Ok(Target::Prefix(parse_hex(value)?)),
+            "suffix" => Ok(Target::Suffix(parse_hex(value)?)),
+            "below" => match value.parse::<f64>() {
+                Ok(difficulty) if difficulty > 0.0 && difficulty <= HASH_NIBBLES as f64 => {
+                    Ok(Target::below(difficulty))
+                }
+                _ => Err(format!(
+                    "Difficulty must be a number above 0 and up to {}, got {}",
+                    HASH_NIBBLES, value
+                )),
+            },
+            _ => Err(format!(
+                "Unknown target {}, use prefix:<hex>, suffix:<hex> or below:<difficulty>",
+                input
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Prefix(nibbles) => write!(f, "prefix:{}", to_hex(nibbles)),
+            Target::Suffix(nibbles) => write!(f, "suffix:{}", to_hex(nibbles)),
+            Target::Below { difficulty, .. } => write!(f, "below:{}", difficulty),
+        }
+    }
+}
+
+// Stored and shown as the spec string
+impl Serialize for Target {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        for spec in ["prefix:00ff", "suffix:cafe", "below:4.5", "below:5"] {
+            assert_eq!(spec.parse::<Target>().unwrap().to_string(), spec);
+        }
+        assert_eq!("PREFIX:0A".parse(), Ok(Target::Prefix(vec![0, 10])));
+        assert_eq!(Target::repeat(0, 3).to_string(), "prefix:000");
+        for spec in [
+            "0",
+            "prefix:",
+            "prefix:xyz",
+            "below:0",
+            "below:65",
+            "around:1",
+        ] {
+            assert!(spec.parse::<Target>().is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_expected_attempts() {
+        assert_eq!(Target::repeat(0, 3).expected_attempts(), 4096.0);
+        assert_eq!(Target::Suffix(vec![1, 2]).expected_attempts(), 256.0);
+        assert_eq!(Target::below(2.0).expected_attempts(), 256.0);
+        assert_eq!(Target::below(0.5).expected_attempts(), 4.0);
+    }
+
+    #[test]
+    fn test_threshold() {
+        // below:2 is a prefix of 2 zeros
+        let mut expected = [0u8; 32];
+        expected[0] = 0x01;
+        assert_eq!(threshold(2.0), expected);
+
+        // below:0.5, 16^-0.5 = 1/4
+        let mut expected = [0u8; 32];
+        expected[0] = 0x40;
+        assert_eq!(threshold(0.5), expected);
+
+        let below = Target::below(2.0);
+        let mut digest = [0xffu8; 32];
+        digest[0] = 0x01;
+        assert!(!below.matches(&digest));
+        digest[0] = 0x00;
+        assert!(below.matches(&digest));
+        assert!(below.matches_hex(&to_hex_bytes(&digest)));
+    }
+}
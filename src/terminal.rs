@@ -0,0 +1,156 @@
+use std::{env, fmt::Write};
+
+use image::{imageops::FilterType, Rgba, RgbaImage};
+
+use crate::error::PokeError;
+
+// Sprites drawn in the terminal with unicode half blocks
+// One character holds 2 pixels stacked : '▀' with the upper pixel as
+// foreground and the lower one as background
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    TrueColor,
+    Ansi256,
+}
+
+impl ColorMode {
+    // Terminals with 24 bits colors set COLORTERM, the others get the 256 palette
+    pub fn detect() -> Self {
+        match env::var("COLORTERM").as_deref() {
+            Ok("truecolor") | Ok("24bit") => ColorMode::TrueColor,
+            _ => ColorMode::Ansi256,
+        }
+    }
+
+    fn fg(&self, pixel: &Rgba<u8>) -> String {
+        match self {
+            ColorMode::TrueColor => format!("\x1b[38;2;{};{};{}m", pixel[0], pixel[1], pixel[2]),
+            ColorMode::Ansi256 => format!("\x1b[38;5;{}m", ansi256(pixel)),
+        }
+    }
+
+    fn bg(&self, pixel: &Rgba<u8>) -> String {
+        match self {
+            ColorMode::TrueColor => format!("\x1b[48;2;{};{};{}m", pixel[0], pixel[1], pixel[2]),
+            ColorMode::Ansi256 => format!("\x1b[48;5;{}m", ansi256(pixel)),
+        }
+    }
+}
+
+// Closest color of the 256 palette : 24 grays or the 6x6x6 color cube
+fn ansi256(pixel: &Rgba<u8>) -> u8 {
+    let [r, g, b, _] = pixel.0;
+    if r == g && g == b {
+        return match r {
+            0..=7 => 16,
+            249..=255 => 231,
+            gray => 232 + ((gray as u16 - 8) * 24 / 241) as u8,
+        };
+    }
+    let cube = |c: u8| (c as u16 * 5 + 127) / 255;
+    (16 + 36 * cube(r) + 6 * cube(g) + cube(b)) as u8
+}
+
+// Wider images (official artwork) are scaled down to 64 columns
+const MAX_WIDTH: u32 = 64;
+const RESET: &str = "\x1b[0m";
+
+fn is_visible(pixel: &Rgba<u8>) -> bool {
+    pixel[3] >= 128
+}
+
+pub fn render_sprite(bytes: &[u8], mode: ColorMode) -> Result<String, PokeError> {
+    let image = image::load_from_memory(bytes)?.to_rgba8();
+    let image = match crop(&image) {
+        Some(image) => image,
+        None => return Ok(String::new()),
+    };
+    let image = if image.width() > MAX_WIDTH {
+        let height = image.height() * MAX_WIDTH / image.width();
+        image::imageops::resize(&image, MAX_WIDTH, height.max(1), FilterType::Nearest)
+    } else {
+        image
+    };
+
+    let transparent = Rgba([0, 0, 0, 0]);
+    let mut out = String::new();
+    for y in (0..image.height()).step_by(2) {
+        for x in 0..image.width() {
+            let top = image.get_pixel(x, y);
+            let bottom = if y + 1 < image.height() {
+                image.get_pixel(x, y + 1)
+            } else {
+                &transparent
+            };
+
+            let _ = match (is_visible(top), is_visible(bottom)) {
+                (true, true) => write!(out, "{}{}▀", mode.fg(top), mode.bg(bottom)),
+                (true, false) => write!(out, "{}{}▀", RESET, mode.fg(top)),
+                (false, true) => write!(out, "{}{}▄", RESET, mode.fg(bottom)),
+                (false, false) => write!(out, "{} ", RESET),
+            };
+        }
+        let _ = writeln!(out, "{}", RESET);
+    }
+
+    Ok(out.trim_end_matches('\n').to_string())
+}
+
+// Transparent borders are dropped, sprites have a lot of them
+fn crop(image: &RgbaImage) -> Option<RgbaImage> {
+    let visible: Vec<(u32, u32)> = image
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| is_visible(pixel))
+        .map(|(x, y, _)| (x, y))
+        .collect();
+
+    let min_x = visible.iter().map(|(x, _)| *x).min()?;
+    let max_x = visible.iter().map(|(x, _)| *x).max()?;
+    let min_y = visible.iter().map(|(_, y)| *y).min()?;
+    let max_y = visible.iter().map(|(_, y)| *y).max()?;
+
+    Some(
+        image::imageops::crop_imm(image, min_x, min_y, max_x - min_x + 1, max_y - min_y + 1)
+            .to_image(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageFormat;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_ansi256() {
+        assert_eq!(ansi256(&Rgba([255, 0, 0, 255])), 196);
+        assert_eq!(ansi256(&Rgba([0, 0, 0, 255])), 16);
+        assert_eq!(ansi256(&Rgba([255, 255, 255, 255])), 231);
+        assert_eq!(ansi256(&Rgba([128, 128, 128, 255])), 243);
+    }
+
+    #[test]
+    fn test_render_sprite() {
+        // Red over blue on the left, transparent over green on the right,
+        // inside a transparent border
+        let mut image = RgbaImage::new(4, 4);
+        image.put_pixel(1, 1, Rgba([255, 0, 0, 255]));
+        image.put_pixel(1, 2, Rgba([0, 0, 255, 255]));
+        image.put_pixel(2, 2, Rgba([0, 255, 0, 255]));
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+
+        assert_eq!(
+            render_sprite(&png, ColorMode::TrueColor).unwrap(),
+            "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m▀\x1b[0m\x1b[38;2;0;255;0m▄\x1b[0m"
+        );
+        assert_eq!(
+            render_sprite(&png, ColorMode::Ansi256).unwrap(),
+            "\x1b[38;5;196m\x1b[48;5;21m▀\x1b[0m\x1b[38;5;46m▄\x1b[0m"
+        );
+        assert!(render_sprite(b"not a png", ColorMode::TrueColor).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    env,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+
+// Client side limits for pokeapi, to stay polite during bulk catches
+// A token bucket shared by every clone of the api spaces the requests, failed
+// requests (5xx, 429, network) are retried with exponential backoff and jitter
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiLimits {
+    // Requests per second on average, 0 disables the limit
+    pub rate: f64,
+    // Requests allowed at once before the rate applies
+    pub burst: u32,
+    pub retries: u32,
+    // Delay before the first retry, doubled on each retry
+    pub backoff: Duration,
+    // Longest wait between two attempts, a longer Retry-After gives up
+    pub max_delay: Duration,
+    // Whole request, body included
+    pub timeout: Duration,
+}
+
+impl Default for ApiLimits {
+    fn default() -> Self {
+        ApiLimits {
+            rate: 20.0,
+            burst: 10,
+            retries: 3,
+            backoff: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ApiLimits {
+    // POKEAPI_RATE, POKEAPI_BURST, POKEAPI_RETRIES, POKEAPI_BACKOFF_MS,
+    // POKEAPI_MAX_DELAY and POKEAPI_TIMEOUT (in seconds) can be set next to DB_URL
+    // Invalid values (a negative or infinite rate...) keep the default
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            env::var(name).ok().and_then(|value| value.parse().ok())
+        }
+        let default = ApiLimits::default();
+
+        ApiLimits {
+            rate: var::<f64>("POKEAPI_RATE")
+                .filter(is_valid_rate)
+                .unwrap_or(default.rate),
+            burst: var("POKEAPI_BURST").unwrap_or(default.burst),
+            retries: var("POKEAPI_RETRIES").unwrap_or(default.retries),
+            backoff: var("POKEAPI_BACKOFF_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(default.backoff),
+            max_delay: var("POKEAPI_MAX_DELAY")
+                .map(Duration::from_secs)
+                .unwrap_or(default.max_delay),
+            timeout: var("POKEAPI_TIMEOUT")
+                .map(Duration::from_secs)
+                .unwrap_or(default.timeout),
+        }
+    }
+
+    // Wait before retry number attempt + 1, None when it is time to give up
+    // Retry-After from the server wins over the computed backoff
+    pub fn retry_delay(&self, attempt: u32, retry_after: Option<u64>) -> Option<Duration> {
+        if attempt >= self.retries {
+            return None;
+        }
+        if let Some(secs) = retry_after {
+            let delay = Duration::from_secs(secs);
+            return (delay <= self.max_delay).then_some(delay);
+        }
+
+        let delay = self
+            .backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        // Jitter keeps concurrent catches from retrying all at once
+        Some(delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0)))
+    }
+}
+
+// Longest single wait for a token, a tiny rate keeps waiting by this much
+const MAX_TOKEN_WAIT: Duration = Duration::from_secs(60);
+
+// Finite and not negative, 0 disables the limit
+fn is_valid_rate(rate: &f64) -> bool {
+    rate.is_finite() && *rate >= 0.0
+}
+
+#[derive(Clone)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    // Available tokens and when they were counted
+    state: Arc<Mutex<(f64, Instant)>>,
+}
+
+impl TokenBucket {
+    pub fn new(rate: f64, burst: u32) -> Self {
+        let burst = burst.max(1) as f64;
+        TokenBucket {
+            rate,
+            burst,
+            state: Arc::new(Mutex::new((burst, Instant::now()))),
+        }
+    }
+
+    // Waits for a token, the lock is never held while sleeping
+    pub async fn acquire(&self) {
+        if self.rate <= 0.0 {
+            return;
+        }
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+                let (tokens, counted_at) = *state;
+                let now = Instant::now();
+                let tokens = (tokens + now.duration_since(counted_at).as_secs_f64() * self.rate)
+                    .min(self.burst);
+                if tokens >= 1.0 {
+                    *state = (tokens - 1.0, now);
+                    return;
+                }
+                *state = (tokens, now);
+                Duration::try_from_secs_f64((1.0 - tokens) / self.rate)
+                    .map_or(MAX_TOKEN_WAIT, |wait| wait.min(MAX_TOKEN_WAIT))
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        let limits = ApiLimits {
+            backoff: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+            ..Default::default()
+        };
+
+        let delay = limits.retry_delay(0, None).unwrap();
+        assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(100));
+        let delay = limits.retry_delay(2, None).unwrap();
+        assert!(delay >= Duration::from_millis(150) && delay <= Duration::from_millis(300));
+        assert_eq!(limits.retry_delay(3, None), None);
+
+        assert_eq!(limits.retry_delay(0, Some(0)), Some(Duration::ZERO));
+        assert_eq!(limits.retry_delay(0, Some(1)), None);
+    }
+
+    #[test]
+    fn test_valid_rate() {
+        assert!(is_valid_rate(&0.0) && is_valid_rate(&1e-300));
+        for rate in ["-1", "inf", "NaN"] {
+            assert!(!is_valid_rate(&rate.parse().unwrap()), "{}", rate);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_token_bucket() {
+        // Burst of 2 then 50 requests per second
+        let bucket = TokenBucket::new(50.0, 2);
+        let start = Instant::now();
+        for _ in 0..2 {
+            bucket.acquire().await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(20));
+        for _ in 0..5 {
+            bucket.acquire().await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(90));
+
+        // A tiny rate waits instead of overflowing the wait duration
+        let bucket = TokenBucket::new(1e-300, 1);
+        bucket.acquire().await;
+        assert!(
+            tokio::time::timeout(Duration::from_millis(20), bucket.acquire())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use core::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::output::Record;
+
+// Owner of a collection, every caught instance belongs to one trainer
+
+pub const DEFAULT_TRAINER: &str = "default";
+
+#[derive(Serialize)]
+pub struct Trainer {
+    pub id: i64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub caught: i64,
+}
+
+impl fmt::Display for Trainer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} : {} caught (since {})",
+            self.name,
+            self.caught,
+            self.created_at.format("%Y-%m-%d")
+        )
+    }
+}
+
+impl Record for Trainer {
+    fn headers() -> Vec<&'static str> {
+        vec!["id", "name", "created_at", "caught"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.name.clone(),
+            self.created_at.to_rfc3339(),
+            self.caught.to_string(),
+        ]
+    }
+}